@@ -0,0 +1,87 @@
+use crate::error::*;
+use crate::interpreter::*;
+use std::rc::Rc;
+
+// The functions every program can call without defining them.
+pub const BUILTINS: &[&str] = &["len", "map", "filter", "reduce", "keys", "values", "has", "remove"];
+
+// The builtins that strings, lists and maps have as methods, called with the
+// value as the first argument: `xs.map(f)` is `map(xs, f)`.
+const METHODS: &[(&str, &[&str])] = &[
+  ("string", &["len"]),
+  ("list", &["len", "map", "filter", "reduce"]),
+  ("map", &["len", "keys", "values", "has", "remove"]),
+];
+
+pub fn method(type_key: &str, name: &str) -> Option<&'static str> {
+  let (_, methods) = METHODS.iter().find(|(key, _)| *key == type_key)?;
+  methods.iter().find(|method| **method == name).copied()
+}
+
+pub fn call(interpreter: &mut Interpreter, name: &str, values: Vec<Value>) -> Result<Value,AsaErrorKind> {
+  match (name, values.as_slice()) {
+    // len(list) or len(string)
+    ("len", [Value::List(list)]) => Ok(Value::Number(list.len() as i32)),
+    ("len", [Value::String(string)]) => Ok(Value::Number(string.chars().count() as i32)),
+    ("len", [Value::Map(map)]) => Ok(Value::Number(map.len() as i32)),
+    // map(list, f) calls f on each element and returns a list of the results
+    ("map", [Value::List(list), f]) => {
+      let mut results = Vec::new();
+      for element in list.iter() {
+        results.push(call_value(interpreter, f, vec![element.clone()])?);
+      }
+      Ok(Value::List(Rc::new(results)))
+    },
+    // filter(list, f) keeps the elements for which f returns true
+    ("filter", [Value::List(list), f]) => {
+      let mut results = Vec::new();
+      for element in list.iter() {
+        match call_value(interpreter, f, vec![element.clone()])? {
+          Value::Bool(true) => results.push(element.clone()),
+          Value::Bool(false) => (),
+          _ => return Err(AsaErrorKind::TypeMismatch),
+        }
+      }
+      Ok(Value::List(Rc::new(results)))
+    },
+    // reduce(list, f, initial) folds the list into one value with f(acc, element)
+    ("reduce", [Value::List(list), f, initial]) => {
+      let mut result = initial.clone();
+      for element in list.iter() {
+        result = call_value(interpreter, f, vec![result, element.clone()])?;
+      }
+      Ok(result)
+    },
+    // keys(map) and values(map) return lists in the order the keys were added
+    ("keys", [Value::Map(map)]) => Ok(Value::List(Rc::new(map.keys().cloned().collect()))),
+    ("values", [Value::Map(map)]) => Ok(Value::List(Rc::new(map.values().cloned().collect()))),
+    // has(map, key) tells whether the map has the key
+    ("has", [Value::Map(map), key]) => Ok(Value::Bool(map.contains(key))),
+    // remove(map, key) returns a copy of the map without the key
+    ("remove", [Value::Map(map), key]) => {
+      let mut map = map.clone();
+      Rc::make_mut(&mut map).remove(key)?;
+      Ok(Value::Map(map))
+    },
+    ("len", [_]) | ("keys", [_]) | ("values", [_]) | ("has", [_, _]) | ("remove", [_, _]) | ("map", [_, _]) | ("filter", [_, _]) | ("reduce", [_, _, _]) => Err(AsaErrorKind::TypeMismatch),
+    _ => Err(AsaErrorKind::Generic(format!("Wrong number of arguments to `{}`", name))),
+  }
+}
+
+// Calls a function value, whether it was defined in the program or is built in.
+pub fn call_value(interpreter: &mut Interpreter, function: &Value, values: Vec<Value>) -> Result<Value,AsaErrorKind> {
+  match function {
+    Value::Function(function) => interpreter.call(function, values),
+    Value::Builtin(name) => call(interpreter, name, values),
+    Value::Constructor(variant) => construct(variant, values),
+    _ => Err(AsaErrorKind::TypeMismatch),
+  }
+}
+
+// Builds a variant of an enum from the values of its fields.
+fn construct(variant: &VariantType, values: Vec<Value>) -> Result<Value,AsaErrorKind> {
+  if values.len() != variant.fields.len() {
+    return Err(AsaErrorKind::Generic(format!("`{}` takes {} values but {} were given", variant.name, variant.fields.len(), values.len())));
+  }
+  Ok(Value::Variant(Rc::new(Variant{enum_name: variant.enum_name.clone(), name: variant.name.clone(), values})))
+}
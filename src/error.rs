@@ -1,11 +1,232 @@
+use crate::lexer::*;
+use crate::interpreter::Value;
+use nom::error::ErrorKind;
+use std::fmt;
+
 #[derive(Debug,PartialEq)]
 pub enum AsaErrorKind {
   UndefinedFunction,
   VariableNotDefined(String),
+  ConstantRedefined(String),
+  MissingArgument(String),
+  UnknownArgument(String),
+  IndexOutOfBounds(i32),
+  KeyNotFound(String),
+  UndefinedType(String),
+  // The struct type and the field name.
+  UnknownField(String, String),
+  MissingField(String, String),
+  UndefinedVariant(String),
+  // The type and the method name.
+  UndefinedMethod(String, String),
+  // A pattern for values that none of the arms of a `match` would match.
+  NonExhaustiveMatch(String),
+  // A value that doesn't have the shape of the pattern it was bound to.
+  PatternMismatch(String),
+  ImmutableVariable(String),
   DivisionByZero,
   NumberOverflow,
   NumberUnderflow,
   TypeMismatch,
   Generic(String),  
   IoError(String),
+  // A value thrown with `throw`.
+  Thrown(Value),
+}
+
+impl AsaErrorKind {
+  // The name of the kind of error, which is what a caught error's `kind` is.
+  pub fn name(&self) -> &'static str {
+    match self {
+      AsaErrorKind::UndefinedFunction => "UndefinedFunction",
+      AsaErrorKind::VariableNotDefined(_) => "VariableNotDefined",
+      AsaErrorKind::ConstantRedefined(_) => "ConstantRedefined",
+      AsaErrorKind::MissingArgument(_) => "MissingArgument",
+      AsaErrorKind::UnknownArgument(_) => "UnknownArgument",
+      AsaErrorKind::IndexOutOfBounds(_) => "IndexOutOfBounds",
+      AsaErrorKind::KeyNotFound(_) => "KeyNotFound",
+      AsaErrorKind::UndefinedType(_) => "UndefinedType",
+      AsaErrorKind::UnknownField(_, _) => "UnknownField",
+      AsaErrorKind::MissingField(_, _) => "MissingField",
+      AsaErrorKind::UndefinedVariant(_) => "UndefinedVariant",
+      AsaErrorKind::UndefinedMethod(_, _) => "UndefinedMethod",
+      AsaErrorKind::NonExhaustiveMatch(_) => "NonExhaustiveMatch",
+      AsaErrorKind::PatternMismatch(_) => "PatternMismatch",
+      AsaErrorKind::ImmutableVariable(_) => "ImmutableVariable",
+      AsaErrorKind::DivisionByZero => "DivisionByZero",
+      AsaErrorKind::NumberOverflow => "NumberOverflow",
+      AsaErrorKind::NumberUnderflow => "NumberUnderflow",
+      AsaErrorKind::TypeMismatch => "TypeMismatch",
+      AsaErrorKind::Generic(_) => "Generic",
+      AsaErrorKind::IoError(_) => "IoError",
+      AsaErrorKind::Thrown(_) => "Thrown",
+    }
+  }
+}
+
+impl fmt::Display for AsaErrorKind {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      AsaErrorKind::UndefinedFunction => write!(f, "undefined name"),
+      AsaErrorKind::VariableNotDefined(name) => write!(f, "variable `{}` is not defined", name),
+      AsaErrorKind::ConstantRedefined(name) => write!(f, "constant `{}` is defined more than once", name),
+      AsaErrorKind::MissingArgument(name) => write!(f, "missing argument `{}`", name),
+      AsaErrorKind::UnknownArgument(name) => write!(f, "unknown argument `{}`", name),
+      AsaErrorKind::IndexOutOfBounds(index) => write!(f, "index {} is out of bounds", index),
+      AsaErrorKind::KeyNotFound(key) => write!(f, "key {} not found", key),
+      AsaErrorKind::UndefinedType(name) => write!(f, "undefined type `{}`", name),
+      AsaErrorKind::UnknownField(name, field) => write!(f, "`{}` has no field `{}`", name, field),
+      AsaErrorKind::MissingField(name, field) => write!(f, "missing field `{}` of `{}`", field, name),
+      AsaErrorKind::UndefinedVariant(name) => write!(f, "undefined variant `{}`", name),
+      AsaErrorKind::UndefinedMethod(name, method) => write!(f, "`{}` has no method `{}`", name, method),
+      AsaErrorKind::NonExhaustiveMatch(pattern) => write!(f, "match does not cover `{}`", pattern),
+      AsaErrorKind::PatternMismatch(message) => write!(f, "{}", message),
+      AsaErrorKind::ImmutableVariable(name) => write!(f, "`{}` cannot be changed here", name),
+      AsaErrorKind::DivisionByZero => write!(f, "division by zero"),
+      AsaErrorKind::NumberOverflow => write!(f, "number overflow"),
+      AsaErrorKind::NumberUnderflow => write!(f, "number underflow"),
+      AsaErrorKind::TypeMismatch => write!(f, "type mismatch"),
+      AsaErrorKind::Generic(message) | AsaErrorKind::IoError(message) => write!(f, "{}", message),
+      AsaErrorKind::Thrown(value) => write!(f, "{}", value),
+    }
+  }
+}
+
+// An error that stopped a program, with the calls that were being made when
+// it happened, the outermost first.
+#[derive(Debug,PartialEq)]
+pub struct RuntimeError {
+  pub kind: AsaErrorKind,
+  pub trace: Vec<CallFrame>,
+}
+
+// A call to a function, and where it was called from. The call to `main`
+// made by the interpreter itself has no location, so its line is 0.
+#[derive(Debug,PartialEq,Clone)]
+pub struct CallFrame {
+  pub function: String,
+  pub line: u32,
+  pub col: u32,
+}
+
+impl fmt::Display for RuntimeError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.kind)?;
+    if !self.trace.is_empty() {
+      write!(f, "\nCall stack (most recent call last):")?;
+    }
+    for frame in &self.trace {
+      match frame.line {
+        0 => write!(f, "\n  `{}`", frame.function)?,
+        line => write!(f, "\n  `{}` called at {}:{}", frame.function, line, frame.col)?,
+      }
+    }
+    Ok(())
+  }
+}
+
+#[derive(Debug,PartialEq,Clone)]
+pub struct LexError {
+  pub kind: LexErrorKind,
+  pub line: u32,
+  pub col: u32,
+}
+
+#[derive(Debug,PartialEq,Clone)]
+pub enum LexErrorKind {
+  UnexpectedCharacter(char),
+  UnterminatedString,
+  UnterminatedComment,
+  NumberOutOfRange(String),
+}
+
+impl fmt::Display for LexError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match &self.kind {
+      LexErrorKind::UnexpectedCharacter(c) => write!(f, "unexpected character `{}`", c)?,
+      LexErrorKind::UnterminatedString => write!(f, "unterminated string")?,
+      LexErrorKind::UnterminatedComment => write!(f, "unterminated block comment")?,
+      LexErrorKind::NumberOutOfRange(number) => write!(f, "number `{}` is out of range", number)?,
+    }
+    write!(f, " at {}:{}", self.line, self.col)
+  }
+}
+
+// A syntax error at a particular token, with the kinds of token that the
+// parser would have accepted there.
+#[derive(Debug,PartialEq,Clone)]
+pub struct SyntaxError {
+  pub line: u32,
+  pub col: u32,
+  pub found: TokenKind,
+  pub found_text: String,
+  pub expected: Vec<TokenKind>,
+  // What the parser was in the middle of, e.g. "after statement".
+  pub context: Option<&'static str>,
+}
+
+impl SyntaxError {
+  pub fn new(input: &Tokens, expected: Vec<TokenKind>) -> SyntaxError {
+    match input.first() {
+      Some(token) => SyntaxError {
+        line: token.start_line,
+        col: token.start_col,
+        found: token.kind,
+        found_text: String::from_utf8_lossy(&token.lexeme).to_string(),
+        expected,
+        context: None,
+      },
+      None => SyntaxError { line: 0, col: 0, found: TokenKind::EOF, found_text: String::new(), expected, context: None },
+    }
+  }
+
+  pub fn is_further_than(&self, other: &SyntaxError) -> bool {
+    (self.line, self.col) > (other.line, other.col)
+  }
+}
+
+impl nom::error::ParseError<Tokens> for SyntaxError {
+  fn from_error_kind(input: Tokens, _kind: ErrorKind) -> Self {
+    SyntaxError::new(&input, vec![])
+  }
+
+  fn append(_input: Tokens, _kind: ErrorKind, other: Self) -> Self {
+    other
+  }
+
+  // Of two alternatives, the one that got further into the input wins. If
+  // both failed at the same token, either of their expected kinds would do.
+  fn or(mut self, other: Self) -> Self {
+    if other.is_further_than(&self) {
+      return other;
+    }
+    if !self.is_further_than(&other) {
+      for kind in other.expected {
+        if !self.expected.contains(&kind) {
+          self.expected.push(kind);
+        }
+      }
+      self.context = self.context.or(other.context);
+    }
+    self
+  }
+}
+
+impl fmt::Display for SyntaxError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let found = match self.found {
+      TokenKind::EOF => "end of input".to_string(),
+      _ => format!("`{}`", self.found_text),
+    };
+    let context = self.context.map(|context| format!(" {}", context)).unwrap_or_default();
+    match self.expected.as_slice() {
+      [] => write!(f, "unexpected {}{}", found, context)?,
+      [kind] => write!(f, "expected {}{}, found {}", kind, context, found)?,
+      kinds => {
+        let kinds: Vec<String> = kinds.iter().map(|kind| kind.to_string()).collect();
+        write!(f, "expected one of {}{}, found {}", kinds.join(", "), context, found)?;
+      }
+    }
+    write!(f, " at {}:{}", self.line, self.col)
+  }
 }
\ No newline at end of file
@@ -1,66 +1,455 @@
-use crate::parser::Node;
-use std::collections::HashMap;
+use crate::parser::{Location, Node};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::rc::{Rc, Weak};
 use crate::error::*;
+use crate::builtins;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Value {
   String(String),
   Number(i32),
   Bool(bool),
+  Function(Rc<Function>),
+  // A function provided by the interpreter, such as `len` or `map`.
+  Builtin(&'static str),
+  // Lists are values like any other: assigning to an element of a list only
+  // changes the variable assigned to, and not other copies of the list.
+  List(Rc<Vec<Value>>),
+  // Maps work the same way as lists.
+  Map(Rc<OrderedMap>),
+  // An instance of a struct, e.g. `Point { x: 1, y: 2 }`.
+  Struct(Rc<Struct>),
+  // A value of an enum type, e.g. `Rect(2, 3)` or `Empty`.
+  Variant(Rc<Variant>),
+  // The function that builds a variant with fields, e.g. `Rect`.
+  Constructor(Rc<VariantType>),
+  // The absence of a value, written `nil`.
+  Nil,
+}
+
+impl Value {
+  // Describes the type of the value, for error messages.
+  pub fn type_name(&self) -> String {
+    match self {
+      Value::String(_) => "a string".to_string(),
+      Value::Number(_) => "a number".to_string(),
+      Value::Bool(_) => "a bool".to_string(),
+      Value::Function(_) | Value::Builtin(_) | Value::Constructor(_) => "a function".to_string(),
+      Value::List(_) => "a list".to_string(),
+      Value::Map(_) => "a map".to_string(),
+      Value::Struct(instance) => format!("a `{}`", instance.name),
+      Value::Variant(variant) => format!("a `{}`", variant.enum_name),
+      Value::Nil => "nil".to_string(),
+    }
+  }
+
+  // The name methods of the value are looked up under.
+  pub fn type_key(&self) -> String {
+    match self {
+      Value::String(_) => "string".to_string(),
+      Value::Number(_) => "number".to_string(),
+      Value::Bool(_) => "bool".to_string(),
+      Value::Function(_) | Value::Builtin(_) | Value::Constructor(_) => "function".to_string(),
+      Value::List(_) => "list".to_string(),
+      Value::Map(_) => "map".to_string(),
+      Value::Struct(instance) => instance.name.clone(),
+      Value::Variant(variant) => variant.enum_name.clone(),
+      Value::Nil => "nil".to_string(),
+    }
+  }
+}
+
+// How values are printed. Strings are printed as they are, unless they are
+// inside a list or other value, where they are quoted.
+impl fmt::Display for Value {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    fn quoted(value: &Value) -> String {
+      match value {
+        Value::String(string) => format!("{:?}", string),
+        value => value.to_string(),
+      }
+    }
+    fn join<'a>(values: impl Iterator<Item = &'a Value>) -> String {
+      values.map(quoted).collect::<Vec<String>>().join(", ")
+    }
+    match self {
+      Value::String(string) => write!(f, "{}", string),
+      Value::Number(number) => write!(f, "{}", number),
+      Value::Bool(bool) => write!(f, "{}", bool),
+      Value::Function(function) => write!(f, "<fn {}>", function.name),
+      Value::Builtin(name) => write!(f, "<fn {}>", name),
+      Value::Constructor(variant) => write!(f, "<fn {}>", variant.name),
+      Value::List(list) => write!(f, "[{}]", join(list.iter())),
+      Value::Map(map) => {
+        let entries: Vec<String> = map.keys().zip(map.values()).map(|(key, value)| format!("{}: {}", quoted(key), quoted(value))).collect();
+        write!(f, "{{{}}}", entries.join(", "))
+      },
+      Value::Struct(instance) => {
+        let fields: Vec<String> = instance.fields.iter().map(|(name, value)| format!("{}: {}", name, quoted(value))).collect();
+        write!(f, "{} {{ {} }}", instance.name, fields.join(", "))
+      },
+      Value::Variant(variant) if variant.values.is_empty() => write!(f, "{}", variant.name),
+      Value::Variant(variant) => write!(f, "{}({})", variant.name, join(variant.values.iter())),
+      Value::Nil => write!(f, "nil"),
+    }
+  }
+}
+
+// A type declared with `struct`, with the names of its fields in the order
+// they were declared.
+#[derive(Debug, PartialEq)]
+pub struct StructType {
+  pub name: String,
+  pub fields: Vec<String>,
+}
+
+// A type declared with `enum`, with its variants in the order they were
+// declared.
+#[derive(Debug, PartialEq)]
+pub struct EnumType {
+  pub name: String,
+  pub variants: Vec<Rc<VariantType>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct VariantType {
+  pub enum_name: String,
+  pub name: String,
+  pub fields: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Variant {
+  pub enum_name: String,
+  pub name: String,
+  pub values: Vec<Value>,
+}
+
+// The fields of a struct instance are kept in the order of the declaration.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Struct {
+  pub name: String,
+  fields: Vec<(String, Value)>,
+}
+
+impl Struct {
+  pub fn get(&self, field: &str) -> Result<&Value,AsaErrorKind> {
+    match self.fields.iter().find(|(name, _)| name == field) {
+      Some((_, value)) => Ok(value),
+      None => Err(AsaErrorKind::UnknownField(self.name.clone(), field.to_string())),
+    }
+  }
+
+  pub fn get_mut(&mut self, field: &str) -> Result<&mut Value,AsaErrorKind> {
+    match self.fields.iter_mut().find(|(name, _)| name == field) {
+      Some((_, value)) => Ok(value),
+      None => Err(AsaErrorKind::UnknownField(self.name.clone(), field.to_string())),
+    }
+  }
+}
+
+// A map from keys to values that remembers the order its keys were first
+// inserted in. Keys can be strings, numbers or bools.
+#[derive(Debug, Clone, Default)]
+pub struct OrderedMap {
+  entries: Vec<(Value, Value)>,
+}
+
+impl OrderedMap {
+  pub fn get(&self, key: &Value) -> Result<&Value,AsaErrorKind> {
+    match self.entries.iter().find(|(k, _)| k == key) {
+      Some((_, value)) => Ok(value),
+      None => Err(missing_key(key)),
+    }
+  }
+
+  pub fn get_mut(&mut self, key: &Value) -> Result<&mut Value,AsaErrorKind> {
+    match self.entries.iter_mut().find(|(k, _)| k == key) {
+      Some((_, value)) => Ok(value),
+      None => Err(missing_key(key)),
+    }
+  }
+
+  pub fn contains(&self, key: &Value) -> bool {
+    self.entries.iter().any(|(k, _)| k == key)
+  }
+
+  // Replacing the value of a key keeps it in its original position.
+  pub fn insert(&mut self, key: Value, value: Value) -> Result<(),AsaErrorKind> {
+    match key {
+      Value::String(_) | Value::Number(_) | Value::Bool(_) => (),
+      _ => return Err(AsaErrorKind::TypeMismatch),
+    }
+    match self.entries.iter_mut().find(|(k, _)| *k == key) {
+      Some((_, old)) => *old = value,
+      None => self.entries.push((key, value)),
+    }
+    Ok(())
+  }
+
+  pub fn remove(&mut self, key: &Value) -> Result<Value,AsaErrorKind> {
+    match self.entries.iter().position(|(k, _)| k == key) {
+      Some(position) => Ok(self.entries.remove(position).1),
+      None => Err(missing_key(key)),
+    }
+  }
+
+  pub fn keys(&self) -> impl Iterator<Item = &Value> {
+    self.entries.iter().map(|(key, _)| key)
+  }
+
+  pub fn values(&self) -> impl Iterator<Item = &Value> {
+    self.entries.iter().map(|(_, value)| value)
+  }
+
+  pub fn len(&self) -> usize {
+    self.entries.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.entries.is_empty()
+  }
+}
+
+// Two maps are equal if they have the same entries, in whatever order.
+impl PartialEq for OrderedMap {
+  fn eq(&self, other: &Self) -> bool {
+    self.len() == other.len() && self.entries.iter().all(|(key, value)| other.get(key) == Ok(value))
+  }
+}
+
+fn missing_key(key: &Value) -> AsaErrorKind {
+  let key = match key {
+    Value::String(string) => format!("{:?}", string),
+    Value::Number(number) => number.to_string(),
+    Value::Bool(bool) => bool.to_string(),
+    other => format!("{:?}", other),
+  };
+  AsaErrorKind::KeyNotFound(key)
 }
 
 type Frame = HashMap<String, Value>;
+// Arguments given by name, in the order they were given.
+type NamedValues = Vec<(String, Value)>;
 type Arguments = Node;
 type Statements = Node;
+type Env = Rc<RefCell<Scope>>;
+
+// A function defined with `fn`, or a lambda. Functions are values too, so
+// they can be stored in variables, passed as arguments and returned.
+pub struct Function {
+  pub name: String,
+  pub arguments: Arguments,
+  pub statements: Statements,
+  // The scope the function was created in, which its body can see. For a
+  // lambda this keeps the variables it captures alive after the call that
+  // created it has returned.
+  env: Env,
+}
+
+// Two functions are only equal if they are the same function.
+impl PartialEq for Function {
+  fn eq(&self, other: &Self) -> bool {
+    std::ptr::eq(self, other)
+  }
+}
+
+// The captured scope is left out, since it may well contain the function.
+impl fmt::Debug for Function {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_struct("Function").field("name", &self.name).finish_non_exhaustive()
+  }
+}
+
+// The variables defined in one function call or block, and the scope around
+// it. Looking up a name searches outwards through the parents.
+#[derive(Debug, Default)]
+pub struct Scope {
+  variables: Frame,
+  parent: Option<Env>,
+  // Whether this is the outermost scope of a function call.
+  function: bool,
+}
+
+impl Scope {
+  fn new_env(parent: &Env) -> Env {
+    Rc::new(RefCell::new(Scope { variables: Frame::new(), parent: Some(parent.clone()), function: false }))
+  }
+
+  fn get(&self, name: &str) -> Option<Value> {
+    match self.variables.get(name) {
+      Some(value) => Some(value.clone()),
+      None => self.parent.as_ref().and_then(|parent| parent.borrow().get(name)),
+    }
+  }
+}
 
 #[derive(Debug)]
 pub struct Interpreter {
   // Function Table:
   // Key - Function name
-  // Value - Vec<Node> arguments, statements
-  functions: HashMap<String, (Arguments,Statements)>,
-  // Stack:
-  // Each element in the stack is a function stack frame.
-  // Crate a new stack frame on function entry.
-  // Pop stack frame on function return.
-  // Key - Variable name
-  // Value - Variable value
-  stack: Vec<Frame>,
+  // Value - The function, with its arguments and statements
+  functions: HashMap<String, Rc<Function>>,
+  // Struct types, by name.
+  types: HashMap<String, Rc<StructType>>,
+  // Enum types, and each of their variants, by name.
+  enums: HashMap<String, Rc<EnumType>>,
+  variants: HashMap<String, Rc<VariantType>>,
+  // Methods defined with `impl`, by type name and then method name.
+  methods: HashMap<String, HashMap<String, Rc<Function>>>,
+  // Scopes:
+  // The global scope holds the variables and constants defined at the top
+  // level, which every function can read. Each function call gets a new scope
+  // whose parent is the scope the function was created in, and each block a
+  // new scope inside the current one. `env` is the scope being run in.
+  globals: Env,
+  env: Env,
+  // Names of the constants in the global scope.
+  constants: HashSet<String>,
+  // Set by a `return` statement and cleared once the enclosing function call
+  // has finished, so that the statements after it are skipped.
+  returning: bool,
+  // The calls being made, the outermost first, and a copy of them taken when
+  // an error first comes out of a call, which is cleared if it is caught.
+  stack: Vec<CallFrame>,
+  trace: Option<Vec<CallFrame>>,
+  // The scopes created since the current function call began, and those
+  // that earlier calls inside it returned closures from. See `release_scopes`.
+  scopes: RefCell<Vec<Weak<RefCell<Scope>>>>,
+}
+
+
+// Scopes still in a cycle with their closures, such as the global scope
+// holding a lambda, are emptied so that they can be freed.
+impl Drop for Interpreter {
+  fn drop(&mut self) {
+    for scope in self.scopes.get_mut().drain(..).filter_map(|scope| scope.upgrade()) {
+      scope.borrow_mut().variables.clear();
+    }
+    self.globals.borrow_mut().variables.clear();
+  }
 }
 
+impl Default for Interpreter {
+  fn default() -> Self {
+    Self::new()
+  }
+}
 
 impl Interpreter {
 
   pub fn new() -> Interpreter {
-    let mut interpreter = Interpreter {
+    let globals: Env = Rc::default();
+    Interpreter {
       functions: HashMap::new(),
+      types: HashMap::new(),
+      enums: HashMap::new(),
+      variants: HashMap::new(),
+      methods: HashMap::new(),
+      env: globals.clone(),
+      globals,
+      constants: HashSet::new(),
+      returning: false,
       stack: Vec::new(),
-    };
-    interpreter.push_new_frame();
-    interpreter
+      trace: None,
+      scopes: RefCell::default(),
+    }
   }  
 
   pub fn push_new_frame(&mut self) {
-    self.stack.push(Frame::new());
+    self.env = self.inner_env();
+  }
+
+  // Runs a block of statements in the given scope. The block evaluates to
+  // its tail expression, or to nil if it has none. A `return` in it stops
+  // the block, which then passes the value returned on.
+  fn exec_block(&mut self, statements: &[Node], env: Env) -> Result<Value,AsaErrorKind> {
+    let previous = std::mem::replace(&mut self.env, env);
+    let mut result = Ok(Value::Nil);
+    for statement in statements {
+      let value = self.exec(statement);
+      if value.is_err() || self.returning || matches!(statement, Node::Tail{..}) {
+        result = value;
+        break;
+      }
+    }
+    self.env = previous;
+    result
+  }
+
+  // A new scope for a block, which can see the variables around it. Those
+  // defined in the block are dropped at the end of it.
+  fn inner_env(&self) -> Env {
+    self.new_env(&self.env)
+  }
+
+  // Struct and enum types share one namespace, since methods are looked up by
+  // the type name.
+  fn check_new_type(&self, name: &str) -> Result<(),AsaErrorKind> {
+    if self.types.contains_key(name) || self.enums.contains_key(name) {
+      return Err(AsaErrorKind::Generic(format!("Type `{}` is defined more than once", name)));
+    }
+    Ok(())
+  }
+
+  fn new_env(&self, parent: &Env) -> Env {
+    let env = Scope::new_env(parent);
+    self.scopes.borrow_mut().push(Rc::downgrade(&env));
+    env
+  }
+
+  // Empties the scopes created since `mark` that nothing can reach any more
+  // now that a function call is over. A closure stored in the scope it was
+  // created in, e.g. `let g = || 1;`, makes a cycle of `Rc`s that would
+  // otherwise never be freed. Only closures refer to scopes, and the only way
+  // for one to outlive the call it was made in is to be returned or thrown
+  // from it, so the scopes the result can't reach are garbage. Those it can
+  // reach are left to the enclosing call to decide on.
+  fn release_scopes(&mut self, mark: usize, result: &Result<Value,AsaErrorKind>) {
+    let created: Vec<Env> = self.scopes.get_mut().drain(mark..).filter_map(|scope| scope.upgrade()).collect();
+    if created.is_empty() {
+      return;
+    }
+    let mut reachable = Vec::new();
+    if let Ok(value) | Err(AsaErrorKind::Thrown(value)) = result {
+      reachable_scopes(value, &created, &mut reachable);
+    }
+    for env in created {
+      if reachable.iter().any(|scope| Rc::ptr_eq(scope, &env)) {
+        self.scopes.get_mut().push(Rc::downgrade(&env));
+      } else {
+        env.borrow_mut().variables.clear();
+      }
+    }
   }
 
   pub fn exec(&mut self, node: &Node) -> Result<Value,AsaErrorKind> {
     match node {
       Node::Program{children} => {
         
-        let mut return_val = Value::Bool(true);
+        let mut return_val = Value::Nil;
+        check_constants(children)?;
+        check_assignments(children)?;
+
+        //functions and types are defined before anything else runs, so that
+        //top-level statements can use ones defined further down. Types come
+        //first of all, so that their methods can be defined.
+        let (mut functions, statements): (Vec<&Node>, Vec<&Node>) = children.iter().partition(|n| matches!(n, Node::FunctionDefine{..} | Node::StructDefine{..} | Node::EnumDefine{..} | Node::ImplDefine{..}));
+        functions.sort_by_key(|n| !matches!(n, Node::StructDefine{..} | Node::EnumDefine{..}));
         
-        //needed to change
-        for n in children {
-          match self.exec(n){
-            Ok(value) => return_val = value,
-            Err(e) => {
-              self.stack.pop(); 
-              return Err(e); 
-            }
-          }
+        for n in functions {
+          self.exec(n)?;
+        }
+        //with the types defined, every `match` can be checked before any of
+        //them runs
+        self.check_matches(node)?;
+        for n in statements {
+          let result = self.exec(n);
+          self.returning = false;
+          return_val = result?;
         }
-        self.stack.pop();
         Ok(return_val)
       },
 
@@ -74,9 +463,10 @@ impl Interpreter {
                 let val2 = self.exec(&children[1])?;
                 match (val1, val2) {
                   (Value::Number(val1), Value::Number(val2)) => {
+                    //a result too large is an overflow and one too small an underflow
                     match name.as_slice() {
-                        b"add" => Ok(Value::Number(val1 + val2)),
-                        b"sub" => Ok(Value::Number(val1 - val2)),
+                        b"add" => val1.checked_add(val2).map(Value::Number).ok_or(if val2 > 0 { AsaErrorKind::NumberOverflow } else { AsaErrorKind::NumberUnderflow }),
+                        b"sub" => val1.checked_sub(val2).map(Value::Number).ok_or(if val2 < 0 { AsaErrorKind::NumberOverflow } else { AsaErrorKind::NumberUnderflow }),
                         _ => Err(AsaErrorKind::Generic("1. Cannot solve mathematical expression".to_string())),
                     }
                   }
@@ -101,60 +491,50 @@ impl Interpreter {
 
 
         match operation {
-          Node::EqualTo {} => {
-            if let (Value::Number(num1), Value::Number(num2)) = (val1, val2) {
-              return Ok(Value::Bool(num1 > num2));
-            } else {
-                return Err(AsaErrorKind::TypeMismatch);
-            }
-          }
-          Node::NotEqualTo {} => {
-            if let (Value::Number(num1), Value::Number(num2)) = (val1, val2) {
-              return Ok(Value::Bool(num1 != num2));
-            } else {
-                return Err(AsaErrorKind::TypeMismatch);
-            }
-          }
+          //values of any type can be compared for equality, and values of
+          //different types are never equal
+          Node::EqualTo {} => Ok(Value::Bool(val1 == val2)),
+          Node::NotEqualTo {} => Ok(Value::Bool(val1 != val2)),
           Node::LessThan {} => {
             if let (Value::Number(num1), Value::Number(num2)) = (val1, val2) {
-              return Ok(Value::Bool(num1 < num2));
+              Ok(Value::Bool(num1 < num2))
             } else {
-                return Err(AsaErrorKind::TypeMismatch);
+                Err(AsaErrorKind::TypeMismatch)
             }
           }
           Node::GreaterThan {} => {
             if let (Value::Number(num1), Value::Number(num2)) = (val1, val2) {
-              return Ok(Value::Bool(num1 > num2));
+              Ok(Value::Bool(num1 > num2))
             } else {
-                return Err(AsaErrorKind::TypeMismatch);
+                Err(AsaErrorKind::TypeMismatch)
             }
           }
           Node::LessThanOrEqualTo {} => {
             if let (Value::Number(num1), Value::Number(num2)) = (val1, val2) {
-              return Ok(Value::Bool(num1 <= num2));
+              Ok(Value::Bool(num1 <= num2))
             } else {
-                return Err(AsaErrorKind::TypeMismatch);
+                Err(AsaErrorKind::TypeMismatch)
             }
           }
           Node::GreaterThanOrEqualTo {} => {
             if let (Value::Number(num1), Value::Number(num2)) = (val1, val2) {
-              return Ok(Value::Bool(num1 >= num2));
+              Ok(Value::Bool(num1 >= num2))
             } else {
-                return Err(AsaErrorKind::TypeMismatch);
+                Err(AsaErrorKind::TypeMismatch)
             }
           }
           Node::And {} => {
             if let (Value::Bool(bool1), Value::Bool(bool2)) = (val1, val2) {
-              return Ok(Value::Bool(bool1 && bool2));
+              Ok(Value::Bool(bool1 && bool2))
             } else {
-                return Err(AsaErrorKind::TypeMismatch);
+                Err(AsaErrorKind::TypeMismatch)
             }          
           }
           Node::Or {} => {
             if let (Value::Bool(bool1), Value::Bool(bool2)) = (val1, val2) {
-              return Ok(Value::Bool(bool1 || bool2));
+              Ok(Value::Bool(bool1 || bool2))
             } else {
-                return Err(AsaErrorKind::TypeMismatch);
+                Err(AsaErrorKind::TypeMismatch)
             }
           }
           _ => unimplemented!() // Handle other operations as needed
@@ -166,18 +546,13 @@ impl Interpreter {
         //case is a conditional expression
         //if true then evaluate the statements and skip the rest of the code
 
-        let mut new_frame = HashMap::new();
-        let mut result: Result<Value, AsaErrorKind> = Err(AsaErrorKind::UndefinedFunction);
+        //if no branch is taken, the if statement has no value of its own
+        let mut result: Result<Value, AsaErrorKind> = Ok(Value::Nil);
 
         let condition = self.exec(&case[0])?;
         
         if let Value::Bool(true) = condition {
-          self.stack.push(new_frame);
-          for n in statements {
-              result = self.exec(&n);
-          }
-          self.stack.pop();
-
+          result = self.exec_block(statements, self.inner_env());
         } else {
 
           for statement in else_statements {
@@ -185,20 +560,12 @@ impl Interpreter {
               Node::ElseIfStatement { case, statements } => {
                   let else_condition = self.exec(&case[0])?;
                   if let Value::Bool(true) = else_condition {
-                      self.stack.push(new_frame);
-                      for statement in statements {
-                          result = self.exec(&statement);
-                      }
-                      self.stack.pop();
+                      result = self.exec_block(statements, self.inner_env());
                       break;
                   }
               }
               Node::ElseStatement { statements } => {
-                  self.stack.push(new_frame);
-                  for statement in statements {
-                      result = self.exec(&statement);
-                  }
-                  self.stack.pop();
+                  result = self.exec_block(statements, self.inner_env());
                   break;
               }
               _ => {
@@ -207,83 +574,330 @@ impl Interpreter {
             }
           }
         }
-        return result;
+        result
+      },
+
+      // Evaluates only the branch picked by the condition, which must be a Bool.
+      Node::TernaryExpression{children} => {
+        match self.exec(&children[0])? {
+          Value::Bool(true) => self.exec(&children[1]),
+          Value::Bool(false) => self.exec(&children[2]),
+          _ => Err(AsaErrorKind::TypeMismatch),
+        }
+      },
+
+      Node::Block{children} => {
+        self.exec_block(children, self.inner_env())
+      },
+
+      Node::Tail{children} => {
+        self.exec(&children[0])
+      },
+
+      Node::Negation{children} => {
+        match self.exec(&children[0])? {
+          Value::Number(value) => value.checked_neg().map(Value::Number).ok_or(AsaErrorKind::NumberOverflow),
+          _ => Err(AsaErrorKind::TypeMismatch),
+        }
+      },
+
+      // Defines a function that takes some arguments and executes a program based on those arguments. The code first checks if the function exists, and if it does, it creates a new scope in which to execute the function's statements. The code then executes each statement in the function's statements list and returns the result of the function's execution. You will have to correlate each passed value with the apprpriate variable in the called function. If the wrong number or an wrong type of variable is passed, return an error. On success, insert the return value of the function (if any) into the appropriate entry of the caller's stack.
+      Node::FunctionCall { name, children, location } => {
+
+        let func_name = String::from_utf8_lossy(name).to_string();
+        //the name may be a function defined with `fn`, a variable holding one,
+        //a builtin or a variant of an enum
+        let function = match self.lookup(&func_name) {
+          Some(function @ (Value::Function(_) | Value::Builtin(_) | Value::Constructor(_))) => function,
+          Some(_) => return Err(AsaErrorKind::TypeMismatch),
+          None => return Err(AsaErrorKind::UndefinedFunction),
+        };
+
+        //arguments are evaluated in the caller's scope
+        let actual_arguments = if let Some(Node::FunctionArguments { children }) = children.first() {children} else {children};
+        let (values, named) = self.arguments(actual_arguments)?;
+        self.traced(func_name, *location, |interpreter| match function {
+          Value::Function(function) => interpreter.call_with_names(&function, values, named),
+          _ if !named.is_empty() => Err(AsaErrorKind::UnknownArgument(named[0].0.clone())),
+          function => builtins::call_value(interpreter, &function, values),
+        })
       },
 
-      // Defines a function that takes some arguments and executes a program based on those arguments. The code first checks if the function exists, and if it does, it creates a new scope in which to execute the function's statements (push a new Frame onto the interpreter stack). The code then executes each statement in the function's statements list and returns the result of the function's execution. You will have to correlate each passed value with the apprpriate variable in the called function. If the wrong number or an wrong type of variable is passed, return an error. On success, insert the return value of the function (if any) into the appropriate entry of the caller's stack.
-      Node::FunctionCall { name, children } => {
+      // Defines a new function based on the elements in the children argument. The name of the function is retrieved from the node struct, the arguments are the first child, and the statements that define the function are the second child. A new key-value pair is then inserted into the functions table of the interprer. If the function was successfully defined, the code returns a Value object with a boolean value of true, otherwise an error is returned.
+      Node::FunctionDefine{name, children} => {
 
-        let mut new_frame = HashMap::new();
-        let mut result: Result<Value, AsaErrorKind> = Err(AsaErrorKind::UndefinedFunction);
-        let rt = self as *mut Interpreter;
-        let func_name = String::from_utf8_lossy(&name).to_string();
-        let (arguments, statements) = self.functions.get(&func_name).map(|(args, body)| (args.clone(), body.clone())).ok_or(AsaErrorKind::UndefinedFunction)?;
-        //handle arguements
+        let arguments = &children[0]; 
+        let statements = &children[1]; 
+        let name = String::from_utf8_lossy(name).to_string();
+        self.functions.insert(name.clone(), Rc::new(Function{name, arguments: arguments.clone(), statements: statements.clone(), env: self.env.clone()}));
+        Ok(Value::Nil)
+      },
 
-        if let Node::FunctionArguments { children: args_children } = arguments {
-          let actual_arguments = if let Some(Node::FunctionArguments { children }) = children.get(0) {children} else {children};
-          if args_children.len() != actual_arguments.len() {
-            return Err(AsaErrorKind::Generic("Number of arguments does not match expected".to_string()));
+      Node::StructDefine{name, children} => {
+        let name = String::from_utf8_lossy(name).to_string();
+        self.check_new_type(&name)?;
+        let mut fields: Vec<String> = Vec::new();
+        for field in children {
+          let field = variable_name(field);
+          if fields.contains(&field) {
+            return Err(AsaErrorKind::Generic(format!("Field `{}` of `{}` is declared more than once", field, name)));
           }
+          fields.push(field);
+        }
+        self.types.insert(name.clone(), Rc::new(StructType{name, fields}));
+        Ok(Value::Nil)
+      },
 
-          //now that I have all of the arguments, insert each into new frame
-          for (arg, value) in args_children.iter().zip(actual_arguments.iter()) {
-            if let Node::Expression { children: expr_children } = arg {
-              if let Some(Node::Identifier { value: name }) = expr_children.first() {
-                let new_value = self.exec(value)?;
-                new_frame.insert(String::from_utf8_lossy(&name).to_string(), new_value);
-              } else {
-                return Err(AsaErrorKind::Generic("Syntax Error".to_string()));
-              }
-            } else {
-              return Err(AsaErrorKind::Generic("Expected argument to be an identifier within an expression".to_string()));
+      Node::EnumDefine{name, children} => {
+        let name = String::from_utf8_lossy(name).to_string();
+        self.check_new_type(&name)?;
+        let mut variants = Vec::new();
+        for variant in children {
+          if let Node::VariantDefine{name: variant, children} = variant {
+            let variant = String::from_utf8_lossy(variant).to_string();
+            if self.variants.contains_key(&variant) {
+              return Err(AsaErrorKind::Generic(format!("Variant `{}` is defined more than once", variant)));
             }
+            let fields = children.iter().map(variable_name).collect();
+            let variant = Rc::new(VariantType{enum_name: name.clone(), name: variant, fields});
+            self.variants.insert(variant.name.clone(), variant.clone());
+            variants.push(variant);
           }
-        } else if !children.is_empty() {
-          return Err(AsaErrorKind::Generic("No Arguments Expected".to_string()));
         }
+        self.enums.insert(name.clone(), Rc::new(EnumType{name, variants}));
+        Ok(Value::Nil)
+      },
 
-        //handle statements
-        self.stack.push(new_frame);
-        let mut last_value = Value::Bool(false);
-        let mut returned = false;
+      // Runs the first arm whose pattern matches the value, with the names the pattern binds defined in a new scope. The arms cover every value of the types their patterns are for, which `check_matches` makes sure of before the program runs, so if none matches the value is of another type.
+      Node::Match{children} => {
+        let arms = self.match_arms(&children[1..])?;
+        let value = self.exec(&children[0])?;
+        for (pattern, body) in arms {
+          let mut bindings = Vec::new();
+          if pattern.matches(&value, &mut bindings) {
+            let env = self.inner_env();
+            env.borrow_mut().variables.extend(bindings);
+            let previous = std::mem::replace(&mut self.env, env);
+            let result = self.exec(body);
+            self.env = previous;
+            return result;
+          }
+        }
+        Err(AsaErrorKind::TypeMismatch)
+      },
 
-        match statements {
-          Node::FunctionStatements { children } => {
-              for n in children {
-                  result = self.exec(&n);
-              }
+      Node::ImplDefine{name, children} => {
+        let name = String::from_utf8_lossy(name).to_string();
+        if !self.types.contains_key(&name) && !self.enums.contains_key(&name) {
+          return Err(AsaErrorKind::UndefinedType(name));
+        }
+        for method in children {
+          if let Node::FunctionDefine{name: method, children} = method {
+            let method = String::from_utf8_lossy(method).to_string();
+            let takes_self = match &children[0] {
+              Node::FunctionArguments{children} => matches!(children.first(), Some(Node::Identifier{value}) if value == b"self"),
+              _ => false,
+            };
+            if !takes_self {
+              return Err(AsaErrorKind::Generic(format!("Method `{}` of `{}` must take `self` as its first parameter", method, name)));
+            }
+            let function = Rc::new(Function{name: format!("{}.{}", name, method), arguments: children[0].clone(), statements: children[1].clone(), env: self.env.clone()});
+            if self.methods.entry(name.clone()).or_default().insert(method.clone(), function).is_some() {
+              return Err(AsaErrorKind::Generic(format!("Method `{}` of `{}` is defined more than once", method, name)));
+            }
           }
-          _ => {
-              todo!();
+        }
+        Ok(Value::Nil)
+      },
+
+      Node::MethodCall{name, children, location} => {
+        let receiver = self.exec(&children[0])?;
+        self.call_method(receiver, name, &children[1], *location)
+      },
+
+      // `a?.b()` is nil if `a` is, and then the arguments aren't evaluated.
+      Node::OptionalMethodCall{name, children, location} => {
+        match self.exec(&children[0])? {
+          Value::Nil => Ok(Value::Nil),
+          receiver => self.call_method(receiver, name, &children[1], *location),
+        }
+      },
+
+      Node::Nil{} => Ok(Value::Nil),
+
+      Node::Throw{children} => {
+        let value = self.exec(&children[0])?;
+        Err(AsaErrorKind::Thrown(value))
+      },
+
+      // Runs the `try` block, and if it fails, runs the `catch` block with the error bound to the pattern in it. The error is an `Error` struct with the kind of error, its message, and the value thrown if it came from a `throw`.
+      Node::TryCatch{children} => {
+        let error = match self.exec(&children[0]) {
+          Ok(value) => return Ok(value),
+          Err(error) => error,
+        };
+        self.trace = None;
+        let env = self.inner_env();
+        let previous = std::mem::replace(&mut self.env, env.clone());
+        let bound = self.bind(&children[1], error_value(error));
+        self.env = previous;
+        bound?;
+        match &children[2] {
+          Node::Block{children} => self.exec_block(children, env),
+          _ => Err(AsaErrorKind::Generic("Expected a block".to_string())),
+        }
+      },
+
+      Node::Coalesce{children} => {
+        match self.exec(&children[0])? {
+          Value::Nil => self.exec(&children[1]),
+          value => Ok(value),
+        }
+      },
+
+      // Builds a struct from a literal, which has to give every field of the type exactly once.
+      Node::StructLiteral{name, children} => {
+        let name = String::from_utf8_lossy(name).to_string();
+        let struct_type = self.types.get(&name).cloned().ok_or_else(|| AsaErrorKind::UndefinedType(name.clone()))?;
+        let mut given: Vec<(String, Value)> = Vec::new();
+        for child in children {
+          if let Node::FieldValue{name: field, children} = child {
+            let field = String::from_utf8_lossy(field).to_string();
+            if !struct_type.fields.contains(&field) {
+              return Err(AsaErrorKind::UnknownField(name, field));
+            }
+            if given.iter().any(|(given, _)| *given == field) {
+              return Err(AsaErrorKind::Generic(format!("Field `{}` was given more than once", field)));
+            }
+            let value = self.exec(&children[0])?;
+            given.push((field, value));
+          }
+        }
+        let mut fields = Vec::new();
+        for field in &struct_type.fields {
+          match given.iter().position(|(given, _)| given == field) {
+            Some(position) => fields.push(given.swap_remove(position)),
+            None => return Err(AsaErrorKind::MissingField(name, field.clone())),
           }
         }
-        self.stack.pop();
-        result  
+        Ok(Value::Struct(Rc::new(Struct{name, fields})))
       },
 
-      // Defines a new function based on the elements in the children argument. The name of the function is retrieved from the node struct, the arguments are the first child, and the statements that define the function are the second child. A new key-value pair is then inserted into the functions table of the interprer. If the function was successfully defined, the code returns a Value object with a boolean value of true, otherwise an error is returned.
-      Node::FunctionDefine{name, children} => {
+      Node::Field{name, children} => {
+        let value = self.exec(&children[0])?;
+        field(value, name)
+      },
 
-        let arguments = &children[0]; 
-        let statements = &children[1]; 
-        self.functions.insert(String::from_utf8_lossy(name).to_string(), (arguments.clone(), statements.clone()));
-        Ok(Value::Bool(true))
+      // `a?.b` is nil if `a` is, rather than an error.
+      Node::OptionalField{name, children} => {
+        match self.exec(&children[0])? {
+          Value::Nil => Ok(Value::Nil),
+          value => field(value, name),
+        }
       },
 
       // Calls the exec() method on the first element in the children argument, which recursively evaluates the AST of the program being executed and returns the resulting value or error message.
       Node::FunctionReturn{children} => {
-        self.exec(&children[0])
+        let value = self.exec(&children[0])?;
+        self.returning = true;
+        Ok(value)
       },
   
-      // Retrieves the value of the identifier from the current frame on the stack. If the variable is defined in the current frame, the code returns its value. If the variable is not defined in the current frame, the code returns an error message.
-      Node::Identifier{value} => {
-        let last = self.stack.len() - 1;
-        let variable_name = String::from_utf8_lossy(&value).to_string();
-        match self.stack[last].get(&variable_name) {
-            Some(id_value) => Ok(id_value.clone()),
-            None => Err(AsaErrorKind::UndefinedFunction),
+      // Creates a function that captures the current scope. It sees the variables of that scope as they are when it is called, including any defined after the lambda itself, and a `let` in its body defines a new local variable rather than changing a captured one.
+      Node::Lambda{children} => {
+        Ok(Value::Function(Rc::new(Function{
+          name: "<lambda>".to_string(),
+          arguments: children[0].clone(),
+          statements: children[1].clone(),
+          env: self.env.clone(),
+        })))
+      },
+
+      Node::List{children} => {
+        let mut values = Vec::new();
+        for child in children {
+          values.push(self.exec(child)?);
+        }
+        Ok(Value::List(Rc::new(values)))
+      },
+
+      Node::Map{children} => {
+        let mut map = OrderedMap::default();
+        for entry in children {
+          if let Node::MapEntry{children} = entry {
+            let key = self.exec(&children[0])?;
+            let value = self.exec(&children[1])?;
+            map.insert(key, value)?;
+          }
+        }
+        Ok(Value::Map(Rc::new(map)))
+      },
+
+      Node::Index{children} => {
+        let target = self.exec(&children[0])?;
+        let index = self.exec(&children[1])?;
+        match (target, index) {
+          (Value::List(list), Value::Number(index)) => Ok(list[list_index(index, list.len())?].clone()),
+          (Value::String(string), Value::Number(index)) => {
+            let chars: Vec<char> = string.chars().collect();
+            Ok(Value::String(chars[list_index(index, chars.len())?].to_string()))
+          },
+          (Value::Map(map), key) => Ok(map.get(&key)?.clone()),
+          _ => Err(AsaErrorKind::TypeMismatch),
+        }
+      },
+
+      // Assigns to an element of a list or map, or a field of a struct, held in a variable, e.g. `xs[0].y = 5`. Assigning to a key a map doesn't have adds it to the end. The variable has to belong to the function being run: globals and variables captured by a lambda can be read but not changed, and neither can constants. check_assignments rejects most such assignments before the program runs.
+      Node::Assignment{children} => {
+        let mut target = &children[0];
+        let mut indexes = Vec::new();
+        loop {
+          target = match target {
+            Node::Index { children } => {
+              indexes.push(Place::Index(self.exec(&children[1])?));
+              &children[0]
+            },
+            Node::Field { name, children } => {
+              indexes.push(Place::Field(String::from_utf8_lossy(name).to_string()));
+              &children[0]
+            },
+            _ => break,
+          };
+        }
+        indexes.reverse();
+        let name = match target {
+          Node::Identifier { value } => String::from_utf8_lossy(value).to_string(),
+          _ => return Err(AsaErrorKind::Generic("Only elements of variables can be assigned to".to_string())),
+        };
+        let value = self.exec(&children[1])?;
+        let env = self.assignable_scope(&name)?;
+        let mut scope = env.borrow_mut();
+        let mut slot = scope.variables.get_mut(&name).unwrap();
+        let last = indexes.len() - 1;
+        for (position, index) in indexes.into_iter().enumerate() {
+          slot = match (slot, index) {
+            (Value::Map(map), Place::Index(key)) if position == last => {
+              Rc::make_mut(map).insert(key, value.clone())?;
+              return Ok(value);
+            },
+            (Value::Map(map), Place::Index(key)) => Rc::make_mut(map).get_mut(&key)?,
+            (Value::Struct(instance), Place::Field(field)) => Rc::make_mut(instance).get_mut(&field)?,
+            (Value::List(list), Place::Index(Value::Number(index))) => {
+              let list = Rc::make_mut(list);
+              let index = list_index(index, list.len())?;
+              &mut list[index]
+            },
+            _ => return Err(AsaErrorKind::TypeMismatch),
+          };
         }
+        *slot = value.clone();
+        Ok(value)
+      },
+
+      // Retrieves the value of the identifier from the current scope or the scopes around it, or from the functions table. If the name is not defined in any of them, the code returns an error message.
+      Node::Identifier{value} => {
+        let variable_name = String::from_utf8_lossy(value).to_string();
+        self.lookup(&variable_name).ok_or(AsaErrorKind::UndefinedFunction)
       },
 
       // Checks the type of the first element in the children argument and deciding what to do based on that type. If the type is a VariableDefine or FunctionReturn node, the code runs the run method on that node and returns the result.
@@ -297,15 +911,19 @@ impl Interpreter {
       }
       },
 
-      // Defines a new variable by assigning a name and a value to it. The name is retrieved from the first element of the children argument, and the value is retrieved by running the run method on the second element of the children argument. The key-value pair is then inserted into the last frame on the stack field of the current runtime object.
+      // Defines a new variable by assigning a name and a value to it. The name is retrieved from the first element of the children argument, and the value is retrieved by running the run method on the second element of the children argument. The key-value pair is then inserted into the current scope of the interpreter.
       Node::VariableDefine{children} => {
-        let name: String = match &children[0] {
-          Node::Identifier { value } => String::from_utf8_lossy(&value).to_string(),
-          _ => "".to_string(),
-        };
         let value = self.exec(&children[1])?;
-        let last = self.stack.len() -1 ;
-        self.stack[last].insert(name, value.clone());
+        self.bind(&children[0], value.clone())?;
+        Ok(value)
+      },
+
+      // Defines a constant in the global frame. Constants can only appear at the top level, and check_constants has already made sure that nothing else at the top level defines the same name.
+      Node::ConstDefine{children} => {
+        let name = variable_name(&children[0]);
+        let value = self.exec(&children[1])?;
+        self.globals.borrow_mut().variables.insert(name.clone(), value.clone());
+        self.constants.insert(name);
         Ok(value)
       },
 
@@ -322,20 +940,699 @@ impl Interpreter {
       Node::Bool{value} => {
         Ok(Value::Bool(*value))
       }, 
-      x => {
+      _ => {
         unimplemented!();
       },
     }
   }
 
+  fn lookup(&self, name: &str) -> Option<Value> {
+    self.env.borrow().get(name)
+      .or_else(|| self.functions.get(name).map(|function| Value::Function(function.clone())))
+      .or_else(|| self.variants.get(name).map(|variant| match variant.fields.len() {
+        0 => Value::Variant(Rc::new(Variant{enum_name: variant.enum_name.clone(), name: variant.name.clone(), values: vec![]})),
+        _ => Value::Constructor(variant.clone()),
+      }))
+      .or_else(|| builtins::BUILTINS.iter().find(|builtin| **builtin == name).map(|builtin| Value::Builtin(builtin)))
+  }
+
+  // Calls a method on a value, passing the value as the first argument.
+  // Methods are looked up by the type of the value, among the methods defined
+  // for it with `impl`, or among the builtins for strings, lists and maps.
+  fn call_method(&mut self, receiver: Value, name: &[u8], arguments: &Node, location: Location) -> Result<Value,AsaErrorKind> {
+    let name = String::from_utf8_lossy(name).to_string();
+    let arguments = match arguments {
+      Node::FunctionArguments{children} => children,
+      _ => return Err(AsaErrorKind::Generic("Expected arguments".to_string())),
+    };
+    let (mut values, named) = self.arguments(arguments)?;
+    let type_key = receiver.type_key();
+    values.insert(0, receiver);
+    self.traced(format!("{}.{}", type_key, name), location, |interpreter| {
+      if let Some(method) = interpreter.methods.get(&type_key).and_then(|methods| methods.get(&name)).cloned() {
+        return interpreter.call_with_names(&method, values, named);
+      }
+      match builtins::method(&type_key, &name) {
+        Some(builtin) if named.is_empty() => builtins::call(interpreter, builtin, values),
+        Some(_) => Err(AsaErrorKind::UnknownArgument(named[0].0.clone())),
+        None => Err(AsaErrorKind::UndefinedMethod(type_key, name)),
+      }
+    })
+  }
+
+  // Makes a call with a frame for it on the call stack. If an error comes out
+  // of the call and none has been recorded yet, the stack is recorded as the
+  // trace of the error.
+  fn traced(&mut self, function: String, location: Location, call: impl FnOnce(&mut Self) -> Result<Value,AsaErrorKind>) -> Result<Value,AsaErrorKind> {
+    self.stack.push(CallFrame{function, line: location.line, col: location.col});
+    let result = call(self);
+    if result.is_err() && self.trace.is_none() {
+      self.trace = Some(self.stack.clone());
+    }
+    self.stack.pop();
+    result
+  }
+
+  // Evaluates the arguments of a call in the current scope, into the
+  // positional ones and the named ones.
+  fn arguments(&mut self, arguments: &[Node]) -> Result<(Vec<Value>, NamedValues),AsaErrorKind> {
+    let mut values = Vec::new();
+    let mut named = Vec::new();
+    for argument in arguments {
+      match argument {
+        Node::NamedArgument { name, children } => {
+          named.push((String::from_utf8_lossy(name).to_string(), self.exec(&children[0])?));
+        }
+        _ if !named.is_empty() => {
+          return Err(AsaErrorKind::Generic("Positional arguments must come before named arguments".to_string()));
+        }
+        Node::SpreadArgument { children } => {
+          match self.exec(&children[0])? {
+            Value::List(list) => values.extend(list.iter().cloned()),
+            _ => return Err(AsaErrorKind::TypeMismatch),
+          }
+        }
+        _ => values.push(self.exec(argument)?),
+      }
+    }
+    Ok((values, named))
+  }
+
+  fn pattern(&self, node: &Node) -> Result<Pattern,AsaErrorKind> {
+    match node {
+      Node::Wildcard{} => Ok(Pattern::Any(None)),
+      Node::Number{value} => Ok(Pattern::Literal(Value::Number(*value))),
+      Node::String{value} => Ok(Pattern::Literal(Value::String(value.clone()))),
+      Node::Bool{value} => Ok(Pattern::Literal(Value::Bool(*value))),
+      Node::Nil{} => Ok(Pattern::Literal(Value::Nil)),
+      Node::Identifier{value} => {
+        let name = String::from_utf8_lossy(value).to_string();
+        match self.variants.get(&name) {
+          Some(variant) if variant.fields.is_empty() => Ok(Pattern::Variant(variant.clone(), vec![])),
+          Some(variant) => Err(AsaErrorKind::Generic(format!("`{}` has {} fields, which the pattern must match", name, variant.fields.len()))),
+          None => Ok(Pattern::Any(Some(name))),
+        }
+      },
+      Node::VariantPattern{name, children} => {
+        let name = String::from_utf8_lossy(name).to_string();
+        let variant = self.variants.get(&name).ok_or(AsaErrorKind::UndefinedVariant(name.clone()))?;
+        if children.len() != variant.fields.len() {
+          return Err(AsaErrorKind::Generic(format!("`{}` has {} fields, which the pattern must match", name, variant.fields.len())));
+        }
+        let fields = children.iter().map(|child| self.pattern(child)).collect::<Result<_,_>>()?;
+        Ok(Pattern::Variant(variant.clone(), fields))
+      },
+      _ => Err(AsaErrorKind::Generic("Invalid pattern".to_string())),
+    }
+  }
+
+  // The pattern and body of each arm of a `match`.
+  fn match_arms<'a>(&self, arms: &'a [Node]) -> Result<Vec<(Pattern, &'a Node)>,AsaErrorKind> {
+    let mut patterns = Vec::new();
+    for arm in arms {
+      if let Node::MatchArm{children} = arm {
+        patterns.push((self.pattern(&children[0])?, &children[1]));
+      }
+    }
+    Ok(patterns)
+  }
+
+  // Checks every `match` in the node: its patterns must be valid, those in
+  // the same place must be for one type if any of them is for an enum or a
+  // bool, and together they must cover every value of those types.
+  fn check_matches(&self, node: &Node) -> Result<(),AsaErrorKind> {
+    if let Node::Match{children} = node {
+      let arms = self.match_arms(&children[1..])?;
+      let patterns: Vec<&Pattern> = arms.iter().map(|(pattern, _)| pattern).collect();
+      check_pattern_types(&patterns)?;
+      let rows: Vec<Vec<&Pattern>> = patterns.into_iter().map(|pattern| vec![pattern]).collect();
+      if let Some(missing) = self.uncovered(&rows, 1) {
+        return Err(AsaErrorKind::NonExhaustiveMatch(missing.join(", ")));
+      }
+    }
+    child_nodes(node).into_iter().try_for_each(|child| self.check_matches(child))
+  }
+
+  // Finds values that none of the rows of patterns match, if there are any,
+  // and returns a pattern for each column that matches them. This goes
+  // through each column in turn: if a column has variants of an enum in it,
+  // each variant of that enum must be covered, and likewise for `true` and
+  // `false`. Other literals can never cover every value on their own.
+  fn uncovered(&self, rows: &[Vec<&Pattern>], width: usize) -> Option<Vec<String>> {
+    if width == 0 {
+      return if rows.is_empty() { Some(vec![]) } else { None };
+    }
+    let any = Pattern::Any(None);
+    let mut cases: Vec<(Pattern, usize, String)> = Vec::new();
+    let enum_name = rows.iter().find_map(|row| match row[0] {
+      Pattern::Variant(variant, _) => Some(&variant.enum_name),
+      _ => None,
+    });
+    if let Some(enum_type) = enum_name.and_then(|name| self.enums.get(name)) {
+      for variant in &enum_type.variants {
+        cases.push((Pattern::Variant(variant.clone(), vec![]), variant.fields.len(), variant.name.clone()));
+      }
+    } else if rows.iter().any(|row| matches!(row[0], Pattern::Literal(Value::Bool(_)))) {
+      for value in [true, false] {
+        cases.push((Pattern::Literal(Value::Bool(value)), 0, value.to_string()));
+      }
+    } else {
+      let rest: Vec<Vec<&Pattern>> = rows.iter().filter(|row| matches!(row[0], Pattern::Any(_))).map(|row| row[1..].to_vec()).collect();
+      let mut missing = self.uncovered(&rest, width - 1)?;
+      missing.insert(0, "_".to_string());
+      return Some(missing);
+    }
+    for (case, arity, name) in cases {
+      // The rows that can match this case, with the patterns for its fields
+      // in place of the first column.
+      let specialized: Vec<Vec<&Pattern>> = rows.iter().filter_map(|row| {
+        let fields: Vec<&Pattern> = match (row[0], &case) {
+          (Pattern::Any(_), _) => vec![&any; arity],
+          (Pattern::Variant(variant, fields), Pattern::Variant(case, _)) if variant.name == case.name => fields.iter().collect(),
+          (Pattern::Literal(value), Pattern::Literal(case)) if value == case => vec![],
+          _ => return None,
+        };
+        Some(fields.into_iter().chain(row[1..].iter().copied()).collect())
+      }).collect();
+      if let Some(mut missing) = self.uncovered(&specialized, arity + width - 1) {
+        let rest = missing.split_off(arity);
+        let first = if arity == 0 { name } else { format!("{}({})", name, missing.join(", ")) };
+        return Some(std::iter::once(first).chain(rest).collect());
+      }
+    }
+    None
+  }
+
+  // Finds the scope defining a variable that the code being run may change.
+  fn assignable_scope(&self, name: &str) -> Result<Env,AsaErrorKind> {
+    let mut env = self.env.clone();
+    loop {
+      if env.borrow().variables.contains_key(name) {
+        if Rc::ptr_eq(&env, &self.globals) && self.constants.contains(name) {
+          return Err(AsaErrorKind::ImmutableVariable(name.to_string()));
+        }
+        return Ok(env);
+      }
+      if env.borrow().function {
+        //defined outside of the function being run, if at all
+        return match self.lookup(name) {
+          Some(_) => Err(AsaErrorKind::ImmutableVariable(name.to_string())),
+          None => Err(AsaErrorKind::VariableNotDefined(name.to_string())),
+        };
+      }
+      let parent = env.borrow().parent.clone();
+      match parent {
+        Some(parent) => env = parent,
+        None => return Err(AsaErrorKind::VariableNotDefined(name.to_string())),
+      }
+    }
+  }
+
+  // Calls a function with arguments that have already been evaluated. Each
+  // argument is bound to the matching parameter in a new scope inside the one
+  // the function was created in, and the function's statements are run there.
+  // This is how builtins such as `map` call functions, so the call goes on
+  // the stack with the location of the call to the builtin.
+  pub fn call(&mut self, function: &Function, values: Vec<Value>) -> Result<Value,AsaErrorKind> {
+    let location = self.stack.last().map(|frame| Location{line: frame.line, col: frame.col}).unwrap_or_default();
+    self.traced(function.name.clone(), location, |interpreter| interpreter.call_with_names(function, values, vec![]))
+  }
+
+  // Like `call`, but some of the arguments may be given by parameter name
+  // after the positional ones.
+  pub fn call_with_names(&mut self, function: &Function, values: Vec<Value>, named: Vec<(String, Value)>) -> Result<Value,AsaErrorKind> {
+    let mark = self.scopes.get_mut().len();
+    let env = self.new_env(&function.env);
+    env.borrow_mut().function = true;
+    //default values are evaluated in the new scope, so that they can refer to
+    //the parameters before them
+    let previous = std::mem::replace(&mut self.env, env.clone());
+    let bound = self.bind_arguments(function, values, named);
+    self.env = previous;
+
+    let result = match (bound, &function.statements) {
+      (Err(error), _) => Err(error),
+      (Ok(()), Node::FunctionStatements { children }) => self.exec_block(children, env),
+      _ => {
+          todo!();
+      }
+    };
+    self.returning = false;
+    self.release_scopes(mark, &result);
+    result
+  }
+
+  // Defines each parameter of the function in the current scope, taking its
+  // value from the positional argument in the same place, from the argument
+  // with its name, or else from its default value. A rest parameter gets a
+  // list of the positional arguments left over.
+  fn bind_arguments(&mut self, function: &Function, mut values: Vec<Value>, named: Vec<(String, Value)>) -> Result<(),AsaErrorKind> {
+    let mut parameters = Vec::new();
+    let mut rest = None;
+    if let Node::FunctionArguments { children } = &function.arguments {
+      for parameter in children {
+        match parameter {
+          Node::RestParameter { children } => {
+            rest = Some(variable_name(&children[0]));
+          }
+          Node::DefaultParameter { children } => parameters.push((&children[0], Some(&children[1]))),
+          parameter => parameters.push((parameter, None)),
+        }
+      }
+    }
+    if let Some(rest) = rest {
+      let extra = values.split_off(values.len().min(parameters.len()));
+      self.env.borrow_mut().variables.insert(rest, Value::List(Rc::new(extra)));
+    } else if values.len() > parameters.len() {
+      return Err(AsaErrorKind::Generic(format!("`{}` takes at most {} arguments but {} were given", function.name, parameters.len(), values.len())));
+    }
+
+    let mut arguments: Vec<Option<Value>> = values.into_iter().map(Some).collect();
+    arguments.resize(parameters.len(), None);
+    for (name, value) in named {
+      let position = parameters.iter().position(|(parameter, _)| pattern_text(parameter) == name).ok_or(AsaErrorKind::UnknownArgument(name.clone()))?;
+      if arguments[position].is_some() {
+        return Err(AsaErrorKind::Generic(format!("Argument `{}` was given more than once", name)));
+      }
+      arguments[position] = Some(value);
+    }
+
+    for ((parameter, default), argument) in parameters.into_iter().zip(arguments) {
+      let value = match (argument, default) {
+        (Some(value), _) => value,
+        (None, Some(default)) => self.exec(default)?,
+        (None, None) => return Err(AsaErrorKind::MissingArgument(pattern_text(parameter))),
+      };
+      self.bind(parameter, value)?;
+    }
+    Ok(())
+  }
+
+  // Defines the names in a pattern in the current scope, taking the value
+  // apart to find the value of each.
+  fn bind(&mut self, pattern: &Node, value: Value) -> Result<(),AsaErrorKind> {
+    match (pattern, value) {
+      (Node::Identifier { value: name }, value) => {
+        self.env.borrow_mut().variables.insert(String::from_utf8_lossy(name).to_string(), value);
+      },
+      (Node::Wildcard {}, _) => (),
+      (Node::ListPattern { children }, Value::List(list)) => {
+        let (elements, rest) = match children.last() {
+          Some(Node::RestParameter { children: rest }) => (&children[..children.len() - 1], Some(&rest[0])),
+          _ => (&children[..], None),
+        };
+        if list.len() < elements.len() || (rest.is_none() && list.len() > elements.len()) {
+          let expected = if rest.is_some() { "at least " } else { "" };
+          return Err(AsaErrorKind::PatternMismatch(format!("`{}` expected a list of {}{} elements, found {}", pattern_text(pattern), expected, elements.len(), list.len())));
+        }
+        for (element, value) in elements.iter().zip(list.iter()) {
+          self.bind(element, value.clone())?;
+        }
+        if let Some(rest) = rest {
+          self.bind(rest, Value::List(Rc::new(list[elements.len()..].to_vec())))?;
+        }
+      },
+      (Node::StructPattern { name, children }, Value::Struct(instance)) if *name == instance.name.as_bytes() => {
+        for field in children {
+          if let Node::FieldPattern { name, children } = field {
+            let value = instance.get(&String::from_utf8_lossy(name))?.clone();
+            self.bind(&children[0], value)?;
+          }
+        }
+      },
+      (Node::ListPattern { .. }, value) => {
+        return Err(AsaErrorKind::PatternMismatch(format!("`{}` expected a list, found {}", pattern_text(pattern), value.type_name())));
+      },
+      (Node::StructPattern { name, .. }, value) => {
+        return Err(AsaErrorKind::PatternMismatch(format!("`{}` expected a `{}`, found {}", pattern_text(pattern), String::from_utf8_lossy(name), value.type_name())));
+      },
+      _ => return Err(AsaErrorKind::Generic("Invalid pattern".to_string())),
+    }
+    Ok(())
+  }
+
+  // Runs a whole program as a script. Top-level statements run in order, and
+  // then `main` is called if the program defines one. The result is the value
+  // returned by `main`, or else the value of the last top-level statement.
+  // If that fails, the error comes with the calls that led to it.
+  pub fn run(&mut self, program: &Node) -> Result<Value,RuntimeError> {
+    self.trace = None;
+    let result = match self.exec(program) {
+      Ok(_) if self.functions.contains_key("main") => self.start_main(vec![]),
+      result => result,
+    };
+    result.map_err(|kind| RuntimeError{kind, trace: self.trace.take().unwrap_or_default()})
+  }
+
   pub fn start_main(&mut self, arguments: Vec<Node>) -> Result<Value,AsaErrorKind> {
     // This node is equivalent to the following Asa program source code:
     // "main()"
     // It calls the main function with a FunctionArguments node as input.
 
-    let start_main = Node::FunctionCall{name: "main".into(), children: arguments};
+    let start_main = Node::FunctionCall{name: "main".into(), children: arguments, location: Location::default()};
     // Call the main function by running this code through the interpreter. 
     self.exec(&start_main)
   }
   
-}
\ No newline at end of file
+}
+
+// The value a caught error is bound to.
+fn error_value(error: AsaErrorKind) -> Value {
+  let kind = Value::String(error.name().to_string());
+  let message = Value::String(error.to_string());
+  let value = match error {
+    AsaErrorKind::Thrown(value) => value,
+    _ => Value::Nil,
+  };
+  let fields = vec![("kind".to_string(), kind), ("message".to_string(), message), ("value".to_string(), value)];
+  Value::Struct(Rc::new(Struct{name: "Error".to_string(), fields}))
+}
+
+fn field(value: Value, name: &[u8]) -> Result<Value,AsaErrorKind> {
+  match value {
+    Value::Struct(instance) => Ok(instance.get(&String::from_utf8_lossy(name))?.clone()),
+    _ => Err(AsaErrorKind::TypeMismatch),
+  }
+}
+
+// A pattern in an arm of a `match`.
+enum Pattern {
+  // Matches anything, binding it to the name if there is one.
+  Any(Option<String>),
+  Literal(Value),
+  Variant(Rc<VariantType>, Vec<Pattern>),
+}
+
+impl Pattern {
+  fn matches(&self, value: &Value, bindings: &mut Vec<(String, Value)>) -> bool {
+    match (self, value) {
+      (Pattern::Any(None), _) => true,
+      (Pattern::Any(Some(name)), value) => {
+        bindings.push((name.clone(), value.clone()));
+        true
+      },
+      (Pattern::Literal(literal), value) => literal == value,
+      (Pattern::Variant(variant, fields), Value::Variant(value)) => {
+        variant.name == value.name && variant.enum_name == value.enum_name
+          && fields.iter().zip(&value.values).all(|(field, value)| field.matches(value, bindings))
+      },
+      _ => false,
+    }
+  }
+}
+
+// Checks that patterns in the same place in the arms of a `match` are all for
+// one type if any of them is for an enum or a bool, so that arms covering each
+// variant or `true` and `false` cover every value there. Literals of other
+// types can be mixed, since a `_` is needed to cover them anyway.
+fn check_pattern_types(patterns: &[&Pattern]) -> Result<(),AsaErrorKind> {
+  let types: Vec<String> = patterns.iter().filter_map(|pattern| match pattern {
+    Pattern::Any(_) => None,
+    Pattern::Literal(value) => Some(value.type_name()),
+    Pattern::Variant(variant, _) => Some(format!("a `{}`", variant.enum_name)),
+  }).collect();
+  let exhaustive = patterns.iter().any(|pattern| matches!(pattern, Pattern::Variant(..) | Pattern::Literal(Value::Bool(_))));
+  if let Some(other) = types.iter().find(|name| exhaustive && *name != &types[0]) {
+    return Err(AsaErrorKind::Generic(format!("Patterns in the same place of a `match` can't be for both {} and {}", types[0], other)));
+  }
+  let mut checked: Vec<&str> = Vec::new();
+  for pattern in patterns {
+    if let Pattern::Variant(variant, fields) = pattern {
+      if checked.contains(&variant.name.as_str()) {
+        continue;
+      }
+      checked.push(&variant.name);
+      for position in 0..fields.len() {
+        let column: Vec<&Pattern> = patterns.iter().filter_map(|other| match other {
+          Pattern::Variant(other, fields) if other.name == variant.name => Some(&fields[position]),
+          _ => None,
+        }).collect();
+        check_pattern_types(&column)?;
+      }
+    }
+  }
+  Ok(())
+}
+
+// One step of the path to the part of a variable being assigned to.
+enum Place {
+  Index(Value),
+  Field(String),
+}
+
+// Writes a pattern out the way it would appear in the program.
+fn pattern_text(node: &Node) -> String {
+  match node {
+    Node::Identifier { value } => String::from_utf8_lossy(value).to_string(),
+    Node::Wildcard {} => "_".to_string(),
+    Node::RestParameter { children } => format!("...{}", pattern_text(&children[0])),
+    Node::ListPattern { children } => {
+      let elements: Vec<String> = children.iter().map(pattern_text).collect();
+      format!("[{}]", elements.join(", "))
+    },
+    Node::StructPattern { name, children } => {
+      let fields: Vec<String> = children.iter().map(pattern_text).collect();
+      format!("{} {{ {} }}", String::from_utf8_lossy(name), fields.join(", "))
+    },
+    Node::FieldPattern { name, children } => match &children[0] {
+      Node::Identifier { value } if value == name => pattern_text(&children[0]),
+      pattern => format!("{}: {}", String::from_utf8_lossy(name), pattern_text(pattern)),
+    },
+    _ => "".to_string(),
+  }
+}
+
+// Adds to `reachable` the scopes out of `created` that the closures in the
+// value can see, along with those the closures stored in them can see. Any
+// scope not in `created` is older than all of them, and so are its parents.
+fn reachable_scopes(value: &Value, created: &[Env], reachable: &mut Vec<Env>) {
+  match value {
+    Value::Function(function) => {
+      let mut env = function.env.clone();
+      while created.iter().any(|scope| Rc::ptr_eq(scope, &env)) && !reachable.iter().any(|scope| Rc::ptr_eq(scope, &env)) {
+        reachable.push(env.clone());
+        for value in env.borrow().variables.values() {
+          reachable_scopes(value, created, reachable);
+        }
+        let parent = env.borrow().parent.clone();
+        match parent {
+          Some(parent) => env = parent,
+          None => break,
+        }
+      }
+    }
+    Value::List(list) => list.iter().for_each(|value| reachable_scopes(value, created, reachable)),
+    Value::Map(map) => map.entries.iter().for_each(|(key, value)| {
+      reachable_scopes(key, created, reachable);
+      reachable_scopes(value, created, reachable);
+    }),
+    Value::Struct(instance) => instance.fields.iter().for_each(|(_, value)| reachable_scopes(value, created, reachable)),
+    Value::Variant(variant) => variant.values.iter().for_each(|value| reachable_scopes(value, created, reachable)),
+    _ => (),
+  }
+}
+
+// The names a pattern defines.
+fn pattern_names(node: &Node, names: &mut Vec<String>) {
+  match node {
+    Node::Identifier { value } => names.push(String::from_utf8_lossy(value).to_string()),
+    Node::ListPattern { children } | Node::StructPattern { children, .. } | Node::FieldPattern { children, .. } | Node::RestParameter { children } | Node::VariantPattern { children, .. } => {
+      for child in children {
+        pattern_names(child, names);
+      }
+    },
+    _ => (),
+  }
+}
+
+fn variable_name(node: &Node) -> String {
+  match node {
+    Node::Identifier { value } => String::from_utf8_lossy(value).to_string(),
+    _ => "".to_string(),
+  }
+}
+
+// Global variables can be read from any function, but never changed by one:
+// a `let` inside a function always defines a new local variable, even if a
+// global of the same name exists. At the top level a `let` may redefine an
+// earlier `let`, but a constant must be the only top-level definition of its
+// name. That is checked here before any of the program runs.
+fn check_constants(children: &[Node]) -> Result<(),AsaErrorKind> {
+  let mut names = std::collections::HashSet::new();
+  let mut constants = std::collections::HashSet::new();
+  for child in children {
+    let mut defined = Vec::new();
+    let constant = match child {
+      Node::ConstDefine { children } => {
+        defined.push(variable_name(&children[0]));
+        true
+      },
+      Node::VariableDefine { children } => {
+        pattern_names(&children[0], &mut defined);
+        false
+      },
+      _ => continue,
+    };
+    for name in defined {
+      if constants.contains(&name) || (constant && names.contains(&name)) {
+        return Err(AsaErrorKind::ConstantRedefined(name));
+      }
+      if constant {
+        constants.insert(name.clone());
+      }
+      names.insert(name);
+    }
+  }
+  Ok(())
+}
+
+// A function can only assign to elements of its own variables: its
+// parameters and the variables it defines. Assigning to a global, or to a
+// variable a lambda captures, is rejected here before any of the program
+// runs, whether or not the function is ever called.
+fn check_assignments(children: &[Node]) -> Result<(),AsaErrorKind> {
+  let mut globals = Vec::new();
+  for child in children {
+    match child {
+      Node::ConstDefine { children } => globals.push(variable_name(&children[0])),
+      Node::VariableDefine { children } => pattern_names(&children[0], &mut globals),
+      _ => (),
+    }
+  }
+  for child in children {
+    check_assignment(child, None, &globals)?;
+  }
+  Ok(())
+}
+
+// Checks the assignments in a node. `locals` are the variables of the
+// function the node is in, if it is in one, and `outer` those of the scopes
+// around that function.
+fn check_assignment(node: &Node, mut locals: Option<&mut Vec<String>>, outer: &[String]) -> Result<(),AsaErrorKind> {
+  match node {
+    Node::FunctionDefine { children, .. } | Node::Lambda { children } => {
+      let mut visible = outer.to_vec();
+      if let Some(locals) = locals {
+        visible.extend(locals.iter().cloned());
+      }
+      let mut parameters = Vec::new();
+      if let Node::FunctionArguments { children } = &children[0] {
+        for parameter in children {
+          match parameter {
+            Node::DefaultParameter { children } => pattern_names(&children[0], &mut parameters),
+            parameter => pattern_names(parameter, &mut parameters),
+          }
+        }
+      }
+      for child in children {
+        check_assignment(child, Some(&mut parameters), &visible)?;
+      }
+    },
+    Node::Assignment { children } => {
+      let name = assigned_variable(&children[0]);
+      if let Some(locals) = &locals {
+        if !name.is_empty() && !locals.contains(&name) {
+          return Err(match outer.contains(&name) {
+            true => AsaErrorKind::ImmutableVariable(name),
+            false => AsaErrorKind::VariableNotDefined(name),
+          });
+        }
+      }
+      for child in children {
+        check_assignment(child, locals.as_deref_mut(), outer)?;
+      }
+    },
+    Node::VariableDefine { children } => {
+      check_assignment(&children[1], locals.as_deref_mut(), outer)?;
+      if let Some(locals) = locals {
+        pattern_names(&children[0], locals);
+      }
+    },
+    Node::Block { children } | Node::ElseStatement { statements: children } => {
+      check_block(children, None, locals, outer)?;
+    },
+    Node::IfStatement { case, statements, else_statements } => {
+      check_assignment(&case[0], locals.as_deref_mut(), outer)?;
+      check_block(statements, None, locals.as_deref_mut(), outer)?;
+      for statement in else_statements {
+        check_assignment(statement, locals.as_deref_mut(), outer)?;
+      }
+    },
+    Node::ElseIfStatement { case, statements } => {
+      check_assignment(&case[0], locals.as_deref_mut(), outer)?;
+      check_block(statements, None, locals, outer)?;
+    },
+    Node::MatchArm { children } => {
+      check_block(&children[1..], Some(&children[0]), locals, outer)?;
+    },
+    Node::TryCatch { children } => {
+      check_assignment(&children[0], locals.as_deref_mut(), outer)?;
+      check_block(&children[2..], Some(&children[1]), locals, outer)?;
+    },
+    node => {
+      for child in child_nodes(node) {
+        check_assignment(child, locals.as_deref_mut(), outer)?;
+      }
+    },
+  }
+  Ok(())
+}
+
+// Checks the statements of a block, in which the names of `pattern`, if
+// there is one, are defined. Variables defined in the block are forgotten at
+// the end of it.
+fn check_block(statements: &[Node], pattern: Option<&Node>, mut locals: Option<&mut Vec<String>>, outer: &[String]) -> Result<(),AsaErrorKind> {
+  let len = locals.as_ref().map(|locals| locals.len());
+  if let (Some(locals), Some(pattern)) = (locals.as_deref_mut(), pattern) {
+    pattern_names(pattern, locals);
+  }
+  for statement in statements {
+    check_assignment(statement, locals.as_deref_mut(), outer)?;
+  }
+  if let (Some(locals), Some(len)) = (locals, len) {
+    locals.truncate(len);
+  }
+  Ok(())
+}
+
+// The variable an assignment changes part of, e.g. `xs` in `xs[0].y = 1`.
+fn assigned_variable(target: &Node) -> String {
+  match target {
+    Node::Index { children } | Node::Field { children, .. } => assigned_variable(&children[0]),
+    node => variable_name(node),
+  }
+}
+
+// The nodes directly inside a node.
+fn child_nodes(node: &Node) -> Vec<&Node> {
+  match node {
+    Node::IfStatement { case, statements, else_statements } => case.iter().chain(statements).chain(else_statements).collect(),
+    Node::ElseIfStatement { case, statements } => case.iter().chain(statements).collect(),
+    Node::ElseStatement { statements } => statements.iter().collect(),
+    Node::Program { children } | Node::Statement { children } | Node::FunctionDefine { children, .. } | Node::Lambda { children }
+    | Node::FunctionArguments { children } | Node::DefaultParameter { children } | Node::NamedArgument { children, .. }
+    | Node::RestParameter { children } | Node::SpreadArgument { children } | Node::FunctionStatements { children }
+    | Node::Expression { children } | Node::MathExpression { children, .. } | Node::FunctionCall { children, .. }
+    | Node::VariableDefine { children } | Node::ConstDefine { children } | Node::FunctionReturn { children }
+    | Node::ConditionalExpression { children } | Node::Block { children } | Node::Tail { children } | Node::Negation { children }
+    | Node::List { children } | Node::Map { children } | Node::MapEntry { children } | Node::Index { children }
+    | Node::Assignment { children } | Node::StructDefine { children, .. } | Node::StructLiteral { children, .. }
+    | Node::FieldValue { children, .. } | Node::Field { children, .. } | Node::EnumDefine { children, .. }
+    | Node::VariantDefine { children, .. } | Node::Match { children } | Node::MatchArm { children }
+    | Node::VariantPattern { children, .. } | Node::ImplDefine { children, .. } | Node::MethodCall { children, .. }
+    | Node::Coalesce { children } | Node::OptionalField { children, .. } | Node::OptionalMethodCall { children, .. }
+    | Node::TryCatch { children } | Node::Throw { children } | Node::ListPattern { children }
+    | Node::StructPattern { children, .. } | Node::FieldPattern { children, .. } | Node::TernaryExpression { children } => children.iter().collect(),
+    _ => vec![],
+  }
+}
+
+// Turns an index into a list of the given length into a position in it.
+// Negative indexes count back from the end of the list.
+pub(crate) fn list_index(index: i32, len: usize) -> Result<usize,AsaErrorKind> {
+  let position = if index < 0 { len as i64 + index as i64 } else { index as i64 };
+  if position < 0 || position >= len as i64 {
+    return Err(AsaErrorKind::IndexOutOfBounds(index));
+  }
+  Ok(position as usize)
+}
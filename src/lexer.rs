@@ -1,6 +1,10 @@
+use crate::error::*;
 use nom::*;
-
-use core::iter::*;
+use std::cell::RefCell;
+use std::fmt;
+use std::ops::Deref;
+use std::rc::Rc;
+use unicode_xid::UnicodeXID;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Token {
@@ -10,6 +14,33 @@ pub struct Token {
   pub end_line: u32,
   pub start_col: u32,
   pub end_col: u32,
+  // Only filled in by `lex_lossless`. Leading trivia is everything between the
+  // previous token's trailing trivia and this token. Trailing trivia runs from
+  // this token up to and including the next newline.
+  pub leading_trivia: Vec<Trivia>,
+  pub trailing_trivia: Vec<Trivia>,
+  // What is wrong with an `Error` token, recorded when it is lexed.
+  pub error: Option<LexErrorKind>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Trivia {
+  pub kind: TriviaKind,
+  pub lexeme: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TriviaKind {
+  WhiteSpace,
+  Newline,
+  LineComment,
+  BlockComment,
+}
+
+impl Default for Token {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Token {
@@ -21,6 +52,9 @@ impl Token {
       end_line: 0,
       start_col: 0,
       end_col: 0,
+      leading_trivia: vec![],
+      trailing_trivia: vec![],
+      error: None,
     }
   }
 
@@ -32,6 +66,25 @@ impl Token {
     self.kind = new_kind;
   }
 
+  // Whether `next` starts right where this token ends, with nothing in
+  // between.
+  pub fn touches(&self, next: &Token) -> bool {
+    self.end_line == next.start_line && self.end_col + 1 == next.start_col
+  }
+
+  // The token's source text, including any trivia attached to it.
+  pub fn to_source(&self) -> Vec<u8> {
+    let mut text = vec![];
+    for trivia in &self.leading_trivia {
+      text.extend_from_slice(&trivia.lexeme);
+    }
+    text.extend_from_slice(&self.lexeme);
+    for trivia in &self.trailing_trivia {
+      text.extend_from_slice(&trivia.lexeme);
+    }
+    text
+  }
+
 }
 
 
@@ -43,7 +96,18 @@ pub enum TokenKind {
   Fn,
   Return,
   Let,
+  Const,
+  Struct,
+  Enum,
+  Match,
+  Impl,
+  Nil,
+  Try,
+  Catch,
+  Throw,
   //------
+  // A whole identifier or number. Inside a string literal each character is
+  // a token of its own instead.
   Alpha,
   Digit,
   LeftParen,
@@ -69,228 +133,532 @@ pub enum TokenKind {
   LessThan,
   GreaterThan,
   Exclaimation,
+  Question,
+  Colon,
+  Dot,
+  LeftBracket,
+  RightBracket,
 
 
   Other,
+  Error,
   EOF,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+impl fmt::Display for TokenKind {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let text = match self {
+      TokenKind::True => "`true`",
+      TokenKind::False => "`false`",
+      TokenKind::Fn => "`fn`",
+      TokenKind::Return => "`return`",
+      TokenKind::Let => "`let`",
+      TokenKind::Const => "`const`",
+      TokenKind::Struct => "`struct`",
+      TokenKind::Enum => "`enum`",
+      TokenKind::Match => "`match`",
+      TokenKind::Impl => "`impl`",
+      TokenKind::Nil => "`nil`",
+      TokenKind::Try => "`try`",
+      TokenKind::Catch => "`catch`",
+      TokenKind::Throw => "`throw`",
+      TokenKind::Alpha => "identifier",
+      TokenKind::Digit => "number",
+      TokenKind::LeftParen => "`(`",
+      TokenKind::RightParen => "`)`",
+      TokenKind::LeftCurly => "`{`",
+      TokenKind::RightCurly => "`}`",
+      TokenKind::Equal => "`=`",
+      TokenKind::Plus => "`+`",
+      TokenKind::Dash => "`-`",
+      TokenKind::Quote => "`\"`",
+      TokenKind::WhiteSpace => "whitespace",
+      TokenKind::Semicolon => "`;`",
+      TokenKind::Comma => "`,`",
+      TokenKind::If => "`if`",
+      TokenKind::Else => "`else`",
+      TokenKind::Slash => "`/`",
+      TokenKind::Asterisk => "`*`",
+      TokenKind::Carot => "`^`",
+      TokenKind::AndSign => "`&`",
+      TokenKind::OrSign => "`|`",
+      TokenKind::LessThan => "`<`",
+      TokenKind::GreaterThan => "`>`",
+      TokenKind::Exclaimation => "`!`",
+      TokenKind::Question => "`?`",
+      TokenKind::Colon => "`:`",
+      TokenKind::Dot => "`.`",
+      TokenKind::LeftBracket => "`[`",
+      TokenKind::RightBracket => "`]`",
+      TokenKind::Other => "character",
+      TokenKind::Error => "invalid character",
+      TokenKind::EOF => "end of input",
+    };
+    write!(f, "{}", text)
+  }
+}
+
+// A view into a shared list of tokens. Cloning a `Tokens` or taking tokens off
+// the front only adjusts the bounds of the view, so the parser never copies
+// the underlying list.
+#[derive(Clone)]
 pub struct Tokens {
-    pub tokens: Vec<Token>,
+    tokens: Rc<[Token]>,
+    start: usize,
+    end: usize,
+    // The furthest syntax error seen so far by any parser working on these
+    // tokens. Combinators such as `many0` throw failed attempts away, so they
+    // are recorded here to be able to report where the parser got stuck.
+    furthest_error: Rc<RefCell<Option<SyntaxError>>>,
+}
+
+impl Default for Tokens {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Tokens {
     pub fn new() -> Tokens {
-        Tokens { tokens: vec![] }
+        Tokens::from(vec![])
     }
 
     pub fn from(tokens: Vec<Token>) -> Tokens {
-        Tokens { tokens }
+        let end = tokens.len();
+        Tokens { tokens: tokens.into(), start: 0, end, furthest_error: Rc::new(RefCell::new(None)) }
     }
 
-    pub fn push(&mut self, token: Token) {
-        self.tokens.push(token);
+    pub fn as_slice(&self) -> &[Token] {
+      &self.tokens[self.start..self.end]
     }
 
     pub fn len(&self) -> usize {
-      self.tokens.len()
+      self.end - self.start
     }
 
     pub fn is_done(&self) -> bool {
-        if !self.is_empty() {
-            match &self.tokens[0].kind {
-                TokenKind::EOF => true,
-                _ => false,
-            }
-        } else {
-            true
+        match self.first() {
+            Some(token) => matches!(token.kind, TokenKind::EOF),
+            None => true,
         }
     }
 
     pub fn is_empty(&self) -> bool {
-      self.tokens.is_empty()
+      self.start == self.end
+    }
+
+    // Returns an error for every `Error` token in the stream.
+    pub fn errors(&self) -> Vec<LexError> {
+      self.iter().filter_map(|tk| {
+        let kind = tk.error.clone()?;
+        Some(LexError { kind, line: tk.start_line, col: tk.start_col })
+      }).collect()
+    }
+
+    // Reassembles the source from the tokens. For a stream produced by
+    // `lex_lossless` this is exactly the original input.
+    pub fn to_source(&self) -> String {
+      let bytes: Vec<u8> = self.iter().flat_map(|tk| tk.to_source()).collect();
+      String::from_utf8_lossy(&bytes).to_string()
     }
 
+    pub fn record_error(&self, error: &SyntaxError) {
+      let mut furthest = self.furthest_error.borrow_mut();
+      *furthest = Some(match furthest.take() {
+        Some(previous) => nom::error::ParseError::or(previous, error.clone()),
+        None => error.clone(),
+      });
+    }
+
+    // Replaces the recorded error with `error` if both are at the same token.
+    // This lets a labelled parser decide what was expected at that point.
+    pub fn relabel_error(&self, error: &SyntaxError) {
+      let mut furthest = self.furthest_error.borrow_mut();
+      match furthest.as_ref() {
+        Some(previous) if previous.is_further_than(error) => (),
+        _ => *furthest = Some(error.clone()),
+      }
+    }
+
+    pub fn take_furthest_error(&self) -> Option<SyntaxError> {
+      self.furthest_error.borrow_mut().take()
+    }
+
+    // Drops the first `count` tokens.
+    pub fn skip(&self, count: usize) -> Tokens {
+      self.slice(count.min(self.len()), self.len())
+    }
+
+    fn slice(&self, start: usize, end: usize) -> Tokens {
+      Tokens {
+        tokens: self.tokens.clone(),
+        start: self.start + start,
+        end: self.start + end,
+        furthest_error: self.furthest_error.clone(),
+      }
+    }
+
+}
+
+impl Deref for Tokens {
+    type Target = [Token];
+
+    fn deref(&self) -> &[Token] {
+        self.as_slice()
+    }
+}
+
+impl PartialEq for Tokens {
+    fn eq(&self, other: &Tokens) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl fmt::Debug for Tokens {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Tokens").field("tokens", &self.as_slice()).finish()
+    }
 }
 
 impl InputLength for Tokens {
     fn input_len(&self) -> usize {
-        self.tokens.len()
+        self.len()
     }
 }
 
 impl InputTake for Tokens{
     fn take(&self, count: usize) -> Self {
-        Tokens {
-            tokens: self.tokens.iter().take(count).cloned().collect(),
-        }
+        self.slice(0, count)
     }
     fn take_split(&self, count: usize) -> (Self, Self) {
-        let (left, right) = self.tokens.split_at(count);
-        (Tokens { tokens: left.to_vec() }, Tokens { tokens: right.to_vec() })
+        (self.slice(count, self.len()), self.slice(0, count))
     }
 }
 
-pub fn split_tokens(input: Tokens) -> IResult<Tokens, Token> {
-  if input.is_empty() {
-      Err(Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::Eof)))
-  } else {
-      let first = input.tokens[0].clone();
-      let rest = input.tokens.into_iter().skip(1).collect();
-      Ok((Tokens::from(rest), first))
+pub type ParseResult<O> = IResult<Tokens, O, SyntaxError>;
+
+pub fn split_tokens(input: Tokens) -> ParseResult<Token> {
+  match input.first() {
+    Some(first) => Ok((input.slice(1, input.len()), first.clone())),
+    None => Err(Err::Error(SyntaxError::new(&input, vec![]))),
   }
 }
 
-pub fn check_token(pred: &dyn Fn(&Token) -> bool) -> impl Fn(Tokens) -> IResult<Tokens, Token> + '_ {
+pub fn check_token(kind: TokenKind) -> impl Fn(Tokens) -> ParseResult<Token> {
   move |input: Tokens| {
-      let parse_res = split_tokens(input.clone())?;
-      if pred(&(parse_res.1)) {
-          Ok(parse_res)
-      } else {
-          combinator::fail(input)
+      match input.first() {
+          Some(token) if token.kind == kind => split_tokens(input),
+          _ => {
+              let error = SyntaxError::new(&input, vec![kind]);
+              input.record_error(&error);
+              Err(Err::Error(error))
+          }
       }
   }
 }
 
-pub fn lex(input: &str) -> Tokens {
-  let mut tokens = Tokens::new();
-  let list = input.as_bytes();
-  let mut i = 0;
-  let mut line = 1;
-  let mut col = 1;
-  let mut diff = 0;
-  while i < list.len() {
-    let c = list[i];
-    let mut kind = match c {
-        48..=57 => TokenKind::Digit,
-        65..=90 | 97..=122 => TokenKind::Alpha,
-        32 | 10 | 9=> TokenKind::WhiteSpace,
-        61 => TokenKind::Equal,
-        59 => TokenKind::Semicolon,
-        123 => TokenKind::LeftCurly,
-        125 => TokenKind::RightCurly,
-        40 => TokenKind::LeftParen,
-        41 => TokenKind::RightParen,
-        43 => TokenKind::Plus,
-        45 => TokenKind::Dash,
-        44 => TokenKind::Comma,
-        34 => TokenKind::Quote,
-
-        //ADDED MAPPING
-        42 => TokenKind::Asterisk,
-        47 => TokenKind::Slash,
-        94 => TokenKind::Carot,
-        124 => TokenKind::OrSign,
-        38 => TokenKind::AndSign,
-        60 => TokenKind::LessThan,
-        62 => TokenKind::GreaterThan,
-        33 => TokenKind::Exclaimation,
-
-        
-        x => TokenKind::Other,
-    };
-    //check if fn
-    if c == b'f' {
-        if i + 1 < list.len(){
-          if list[i + 1] == b'n' {
-            kind = TokenKind::Fn;
-            i += 1;
-            diff = 1;
-          }
-        }
+// Maps a single character to its token kind. Characters that can start an
+// identifier are handled separately by `lex`, since they may form keywords.
+fn char_kind(c: char) -> TokenKind {
+  match c {
+    '0'..='9' => TokenKind::Digit,
+    '=' => TokenKind::Equal,
+    ';' => TokenKind::Semicolon,
+    '{' => TokenKind::LeftCurly,
+    '}' => TokenKind::RightCurly,
+    '(' => TokenKind::LeftParen,
+    ')' => TokenKind::RightParen,
+    '+' => TokenKind::Plus,
+    '-' => TokenKind::Dash,
+    ',' => TokenKind::Comma,
+    '"' => TokenKind::Quote,
+
+    //ADDED MAPPING
+    '*' => TokenKind::Asterisk,
+    '/' => TokenKind::Slash,
+    '^' => TokenKind::Carot,
+    '|' => TokenKind::OrSign,
+    '&' => TokenKind::AndSign,
+    '<' => TokenKind::LessThan,
+    '>' => TokenKind::GreaterThan,
+    '!' => TokenKind::Exclaimation,
+    '?' => TokenKind::Question,
+    ':' => TokenKind::Colon,
+    '.' => TokenKind::Dot,
+    '[' => TokenKind::LeftBracket,
+    ']' => TokenKind::RightBracket,
+
+    c if c.is_whitespace() => TokenKind::WhiteSpace,
+    c if c.is_alphabetic() => TokenKind::Alpha,
+    _ => TokenKind::Other,
+  }
+}
+
+fn keyword(word: &str) -> Option<TokenKind> {
+  match word {
+    "fn" => Some(TokenKind::Fn),
+    "if" => Some(TokenKind::If),
+    "else" => Some(TokenKind::Else),
+    "true" => Some(TokenKind::True),
+    "false" => Some(TokenKind::False),
+    "let" => Some(TokenKind::Let),
+    "const" => Some(TokenKind::Const),
+    "struct" => Some(TokenKind::Struct),
+    "enum" => Some(TokenKind::Enum),
+    "match" => Some(TokenKind::Match),
+    "impl" => Some(TokenKind::Impl),
+    "nil" => Some(TokenKind::Nil),
+    "try" => Some(TokenKind::Try),
+    "catch" => Some(TokenKind::Catch),
+    "throw" => Some(TokenKind::Throw),
+    "return" => Some(TokenKind::Return),
+    _ => None,
+  }
+}
+
+fn is_ident_start(c: char) -> bool {
+  c == '_' || c.is_xid_start()
+}
+
+// Tracks the current position while walking the input. Lines and columns are
+// both 1-based, and columns count characters rather than bytes.
+struct Cursor {
+  chars: Vec<char>,
+  i: usize,
+  line: u32,
+  col: u32,
+}
+
+impl Cursor {
+  fn peek(&self, offset: usize) -> Option<char> {
+    self.chars.get(self.i + offset).copied()
+  }
+
+  // Builds a token from the next `len` characters and advances past them.
+  fn take(&mut self, kind: TokenKind, len: usize) -> Token {
+    let text: String = self.chars[self.i..self.i + len].iter().collect();
+    let (start_line, start_col) = (self.line, self.col);
+    let (mut end_line, mut end_col) = (self.line, self.col);
+    for _ in 0..len {
+      end_line = self.line;
+      end_col = self.col;
+      if self.chars[self.i] == '\n' {
+        self.line += 1;
+        self.col = 1;
+      } else {
+        self.col += 1;
+      }
+      self.i += 1;
+    }
+    Token {
+      kind,
+      lexeme: text.into_bytes(),
+      start_line,
+      end_line,
+      start_col,
+      end_col,
+      leading_trivia: vec![],
+      trailing_trivia: vec![],
+      error: None,
     }
-    //check if
-    if c == b'i' {
-      if i + 1 < list.len(){
-        if list[i + 1] == b'f' {
-          kind = TokenKind::If;
-          i += 1;
-          diff = 1;
+  }
+
+  // Builds an `Error` token from the next `len` characters.
+  fn take_error(&mut self, error: LexErrorKind, len: usize) -> Token {
+    Token { error: Some(error), ..self.take(TokenKind::Error, len) }
+  }
+
+  fn take_trivia(&mut self, kind: TriviaKind, len: usize) -> Trivia {
+    Trivia { kind, lexeme: self.take(TokenKind::WhiteSpace, len).lexeme }
+  }
+
+  // Returns the kind and length of the whitespace run starting at the cursor.
+  // Newlines are kept apart from other whitespace.
+  fn whitespace(&self) -> Option<(TriviaKind, usize)> {
+    match (self.peek(0), self.peek(1)) {
+      (Some('\n'), _) => Some((TriviaKind::Newline, 1)),
+      (Some('\r'), Some('\n')) => Some((TriviaKind::Newline, 2)),
+      (Some(c), _) if c.is_whitespace() => {
+        let mut len = 1;
+        while self.peek(len).is_some_and(|c| c.is_whitespace() && c != '\n' && !(c == '\r' && self.peek(len + 1) == Some('\n'))) {
+          len += 1;
         }
+        Some((TriviaKind::WhiteSpace, len))
       }
+      _ => None,
     }
-    //check else
-    if c == b'e' {
-      if i + 3 < list.len(){
-        if list[i + 1] == b'l' && list[i + 2] == b's' && list[i + 3] == b'e' {
-          kind = TokenKind::Else;
-          i += 3;
-          diff = 3;
+  }
+
+  // Returns the kind and length of the comment starting at the cursor, if any. Line
+  // comments run up to (but not including) the next newline, and block
+  // comments may be nested. An unterminated block comment is not a comment.
+  fn comment(&self) -> Option<(TriviaKind, usize)> {
+    match (self.peek(0), self.peek(1)) {
+      (Some('/'), Some('/')) => {
+        let mut len = 2;
+        while self.peek(len).is_some_and(|c| c != '\n') {
+          len += 1;
         }
+        Some((TriviaKind::LineComment, len))
       }
-    }
-    //check if true
-    if c == b't' {
-      if i + 3 < list.len(){
-        if list[i + 1] == b'r' && list[i + 2] == b'u' && list[i + 3] == b'e' {
-          kind = TokenKind::True;
-          i += 3;
-          diff = 3;
+      (Some('/'), Some('*')) => {
+        let mut len = 2;
+        let mut depth = 1;
+        while depth > 0 {
+          match (self.peek(len), self.peek(len + 1)) {
+            (Some('/'), Some('*')) => {
+              depth += 1;
+              len += 2;
+            }
+            (Some('*'), Some('/')) => {
+              depth -= 1;
+              len += 2;
+            }
+            (Some(_), _) => len += 1,
+            (None, _) => return None,
+          }
         }
+        Some((TriviaKind::BlockComment, len))
       }
+      _ => None,
     }
-    //check if false
-    if c == b'f' {
-      if i + 4 < list.len(){
-        if list[i + 1] == b'a' && list[i + 2] == b'l' && list[i + 3] == b's' && list[i + 4] == b'e' {
-          kind = TokenKind::False;
-          i += 4;
-          diff = 4;
-        }
+  }
+}
+
+// Attaches trivia to the tokens around it while lexing in lossless mode.
+struct TriviaCollector {
+  lossless: bool,
+  pending: Vec<Trivia>,
+  // Whether trivia still belongs to the previous token, i.e. no newline has
+  // been seen since it.
+  trailing: bool,
+}
+
+impl TriviaCollector {
+  fn trivia(&mut self, tokens: &mut [Token], trivia: Trivia) {
+    if !self.lossless {
+      return;
+    }
+    match tokens.last_mut() {
+      Some(last) if self.trailing => {
+        self.trailing = trivia.kind != TriviaKind::Newline;
+        last.trailing_trivia.push(trivia);
       }
+      _ => self.pending.push(trivia),
     }
-    //check if let
-    if c == b'l' { 
-      if i + 2 < list.len(){
-        if list[i + 1] == b'e' && list[i + 2] == b't' {
-          kind = TokenKind::Let;
-          i += 2;
-          diff = 2;
-        }
+  }
+
+  fn token(&mut self, tokens: &mut Vec<Token>, mut token: Token) {
+    token.leading_trivia = std::mem::take(&mut self.pending);
+    self.trailing = true;
+    tokens.push(token);
+  }
+}
+
+pub fn lex(input: &str) -> Tokens {
+  lex_with_trivia(input, false)
+}
+
+// Lexes the input without discarding anything: whitespace, newlines and
+// comments are attached to the neighbouring tokens as trivia, so that
+// `Tokens::to_source` reproduces the input byte-for-byte.
+pub fn lex_lossless(input: &str) -> Tokens {
+  lex_with_trivia(input, true)
+}
+
+fn lex_with_trivia(input: &str, lossless: bool) -> Tokens {
+  let mut tokens = vec![];
+  let mut cursor = Cursor { chars: input.chars().collect(), i: 0, line: 1, col: 1 };
+  let mut collector = TriviaCollector { lossless, pending: vec![], trailing: false };
+  let mut in_string = false;
+  let mut string_start = 0;
+
+  while let Some(c) = cursor.peek(0) {
+    // Inside a string literal every character is kept, including whitespace,
+    // so that the parser can rebuild its contents exactly.
+    if in_string {
+      let kind = char_kind(c);
+      if kind == TokenKind::Quote {
+        in_string = false;
       }
+      collector.token(&mut tokens, cursor.take(kind, 1));
+      continue;
     }
-    //check if return
-    if c == b'r' {
-      if i + 5 < list.len(){
-        if list[i + 1] == b'e' && list[i + 2] == b't' && list[i + 3] == b'u' && list[i + 4] == b'r' && list[i + 5] == b'n' {
-          kind = TokenKind::Return;
-          i += 5;
-          diff = 5;
-        }
+
+    if is_ident_start(c) {
+      // Identifiers follow the Unicode XID rules. A whole word is read first so
+      // that keywords are only recognized when they stand on their own.
+      let mut len = 1;
+      while cursor.peek(len).is_some_and(|c| c.is_xid_continue()) {
+        len += 1;
       }
+      let word: String = cursor.chars[cursor.i..cursor.i + len].iter().collect();
+      let kind = keyword(&word).unwrap_or(TokenKind::Alpha);
+      collector.token(&mut tokens, cursor.take(kind, len));
+      continue;
     }
 
-    //create token struct
-    let token = Token {
-        kind,
-        lexeme: vec![c],
-        start_col: col,
-        end_col: col + diff,
-        start_line: line,
-        end_line: line,
-    };
-    i +=1;
-    diff = 0;
-    tokens.push(token.clone());
-    col +=1;
-    if c == 10{
-      line +=1;
-      col = 1;
+    // Numbers have to fit in an i32.
+    if c.is_ascii_digit() {
+      let mut len = 1;
+      while cursor.peek(len).is_some_and(|c| c.is_ascii_digit()) {
+        len += 1;
+      }
+      let number: String = cursor.chars[cursor.i..cursor.i + len].iter().collect();
+      let token = match number.parse::<i32>() {
+        Ok(_) => cursor.take(TokenKind::Digit, len),
+        Err(_) => cursor.take_error(LexErrorKind::NumberOutOfRange(number), len),
+      };
+      collector.token(&mut tokens, token);
+      continue;
+    }
+
+    // Comments are trivia, just like whitespace.
+    if let Some((kind, len)) = cursor.comment().or_else(|| cursor.whitespace()) {
+      let trivia = cursor.take_trivia(kind, len);
+      collector.trivia(&mut tokens, trivia);
+      continue;
+    }
+
+    // An unterminated block comment swallows the rest of the input.
+    if let (Some('/'), Some('*')) = (cursor.peek(0), cursor.peek(1)) {
+      let len = cursor.chars.len() - cursor.i;
+      collector.token(&mut tokens, cursor.take_error(LexErrorKind::UnterminatedComment, len));
+      continue;
+    }
+
+    match char_kind(c) {
+      TokenKind::Quote => {
+        in_string = true;
+        string_start = tokens.len();
+        collector.token(&mut tokens, cursor.take(TokenKind::Quote, 1));
+      }
+      // Characters without a token of their own are only valid inside a
+      // string. This includes alphabetic characters that cannot start an
+      // identifier, such as combining marks. They are reported as errors and
+      // lexing carries on.
+      TokenKind::Alpha | TokenKind::Other => collector.token(&mut tokens, cursor.take_error(LexErrorKind::UnexpectedCharacter(c), 1)),
+      kind => collector.token(&mut tokens, cursor.take(kind, 1)),
     }
   }
 
+  if in_string {
+    tokens[string_start].kind = TokenKind::Error;
+    tokens[string_start].error = Some(LexErrorKind::UnterminatedString);
+  }
+
+  // Whatever trivia is left over leads up to the end of the input.
   let token = Token {
     kind: TokenKind::EOF,
     lexeme: vec![],
-    start_col: col,
-    end_col: col ,
-    start_line: line,
-    end_line: line,
-    };
-  tokens.push(token);
-
-  
-  let filtered_tokens: Vec<Token> = tokens.tokens.iter().filter(|tkn| tkn.kind != TokenKind::WhiteSpace).cloned().collect();
-  Tokens::from(filtered_tokens)
+    start_col: cursor.col,
+    end_col: cursor.col,
+    start_line: cursor.line,
+    end_line: cursor.line,
+    leading_trivia: vec![],
+    trailing_trivia: vec![],
+    error: None,
+  };
+  collector.token(&mut tokens, token);
+  Tokens::from(tokens)
 }
@@ -1,6 +1,7 @@
 extern crate nom;
 
 pub mod interpreter;
+mod builtins;
 pub mod parser;
 pub mod error;
 pub mod lexer;
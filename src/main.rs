@@ -13,21 +13,28 @@ fn main() -> Result<(), AsaErrorKind> {
   let filename = &args[1];
   let content = fs::read_to_string(filename).map_err(|e| AsaErrorKind::IoError(e.to_string()))?;
   let tokens = lex(&content);
-  match program(tokens) {
-    Ok((tokens, tree)) => {
+  let errors = tokens.errors();
+  if !errors.is_empty() {
+    for error in errors {
+      println!("Error: {}", error);
+    }
+    return Ok(());
+  }
+  match parse(tokens) {
+    Ok(tree) => {
       //println!("{:?}", tokens);
       //println!("Tree: {:#?}", tree);
       let mut interpreter = Interpreter::new();
-      let result = interpreter.exec(&tree);
-
-      let main_result = interpreter.start_main(vec![]);
-      if let Ok(value) = main_result {
-        println!("{:?}", value);
-    } else {
-        println!("An error occurred.");
-    }
+      match interpreter.run(&tree) {
+        Ok(value) => println!("{}", value),
+        Err(error) => println!("Error: {}", error),
+      }
+    },
+    Err(errors) => {
+      for error in errors {
+        println!("Error: {}", error);
+      }
     },
-    Err(e) => println!("Error: {:?}", e),
   }
 
   Ok(())
@@ -4,19 +4,31 @@
 
 //use nom::*;
 use crate::lexer::*;
-use nom::sequence::tuple;
-use nom::combinator::map;
+use crate::error::*;
 
 
  use nom::{
-  IResult,
+  Err,
+  InputTake,
   branch::alt,
   combinator::opt,
   multi::{many1, many0},
-  bytes::complete::{tag},
-  character::complete::{alphanumeric1, digit1},
 };
  
+// Where something starts in the source, for error messages.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Location {
+  pub line: u32,
+  pub col: u32,
+}
+
+fn location(input: &Tokens) -> Location {
+  match input.first() {
+    Some(token) => Location{line: token.start_line, col: token.start_col},
+    None => Location::default(),
+  }
+}
+
 // Here are the different node types. You will use these to make your parser.
 // You may add other nodes as you see fit.
 
@@ -25,18 +37,24 @@ pub enum Node {
   Program { children: Vec<Node> },
   Statement { children: Vec<Node> },
   FunctionDefine {name: Vec<u8>, children: Vec<Node> },
+  Lambda { children: Vec<Node> },
   FunctionArguments { children: Vec<Node> },
+  DefaultParameter { children: Vec<Node> },
+  NamedArgument { name: Vec<u8>, children: Vec<Node> },
+  RestParameter { children: Vec<Node> },
+  SpreadArgument { children: Vec<Node> },
   FunctionStatements { children: Vec<Node> },
   Expression { children: Vec<Node> },
   MathExpression {name: Vec<u8>, children: Vec<Node> },
-  FunctionCall { name: Vec<u8>, children: Vec<Node> },
+  FunctionCall { name: Vec<u8>, children: Vec<Node>, location: Location },
   VariableDefine { children: Vec<Node> },
+  ConstDefine { children: Vec<Node> },
   FunctionReturn { children: Vec<Node> },
   Number { value: i32 },
   Bool { value: bool },
+  Nil {},
   Identifier { value: Vec<u8> },
   String { value: String },
-  Comment { value: Vec<u8> },
 
 
   //For Final
@@ -59,6 +77,50 @@ pub enum Node {
   ElseIfStatement{case: Vec<Node>, statements: Vec<Node>},
   ElseStatement{statements: Vec<Node>},
 
+  //Blocks and unary minus, usable as expressions
+  Block{children: Vec<Node>},
+  // The expression a block ends with, without a `;` after it, which gives
+  // the block its value. It is always the last of the block's statements.
+  Tail{children: Vec<Node>},
+  Negation{children: Vec<Node>},
+  List{children: Vec<Node>},
+  Map{children: Vec<Node>},
+  MapEntry{children: Vec<Node>},
+  Index{children: Vec<Node>},
+  Assignment{children: Vec<Node>},
+
+  //Structs
+  StructDefine{name: Vec<u8>, children: Vec<Node>},
+  StructLiteral{name: Vec<u8>, children: Vec<Node>},
+  FieldValue{name: Vec<u8>, children: Vec<Node>},
+  Field{name: Vec<u8>, children: Vec<Node>},
+
+  //Enums and pattern matching
+  EnumDefine{name: Vec<u8>, children: Vec<Node>},
+  VariantDefine{name: Vec<u8>, children: Vec<Node>},
+  Match{children: Vec<Node>},
+  MatchArm{children: Vec<Node>},
+  VariantPattern{name: Vec<u8>, children: Vec<Node>},
+  Wildcard{},
+
+  //Methods
+  ImplDefine{name: Vec<u8>, children: Vec<Node>},
+  MethodCall{name: Vec<u8>, children: Vec<Node>, location: Location},
+
+  //Nil handling: `a ?? b`, `a?.b` and `a?.b()`
+  Coalesce{children: Vec<Node>},
+  OptionalField{name: Vec<u8>, children: Vec<Node>},
+  OptionalMethodCall{name: Vec<u8>, children: Vec<Node>, location: Location},
+
+  //Error handling
+  TryCatch{children: Vec<Node>},
+  Throw{children: Vec<Node>},
+
+  //Destructuring
+  ListPattern{children: Vec<Node>},
+  StructPattern{name: Vec<u8>, children: Vec<Node>},
+  FieldPattern{name: Vec<u8>, children: Vec<Node>},
+  TernaryExpression{children: Vec<Node>},
 
   Null,
 }
@@ -66,177 +128,128 @@ pub enum Node {
 // Some helper functions to use Tokens instead of a &str with Nom. 
 // You'll probably have to create more of these as needed.
 
-pub fn t_alpha(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::Alpha => true,
-    _ => false,
-  });
-  fxn(input.clone())
+pub fn t_alpha(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::Alpha);
+  fxn(input)
 }
 
-pub fn t_digit(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::Digit => true,
-    _ => false,
-  });
-  fxn(input.clone())
+pub fn t_digit(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::Digit);
+  fxn(input)
 }
 
-pub fn t_true(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::True => true,
-    _ => false,
-  });
-  fxn(input.clone())
+pub fn t_true(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::True);
+  fxn(input)
 }
 
-pub fn t_false(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::False => true,
-    _ => false,
-  });
-  fxn(input.clone())
+pub fn t_false(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::False);
+  fxn(input)
 }
 
-pub fn t_alpha1(input: Tokens) -> IResult<Tokens, Vec<Token>> {
+pub fn t_alpha1(input: Tokens) -> ParseResult<Vec<Token>> {
   many1(t_alpha)(input)
 }
 
-pub fn t_alpha0(input: Tokens) -> IResult<Tokens, Vec<Token>> {
+pub fn t_alpha0(input: Tokens) -> ParseResult<Vec<Token>> {
   many0(t_alpha)(input)
 }
 
-pub fn t_alphanumeric1(input: Tokens) -> IResult<Tokens, Vec<Token>> {
+pub fn t_alphanumeric1(input: Tokens) -> ParseResult<Vec<Token>> {
   many1(alt((t_alpha,t_digit)))(input)
 }
 
-pub fn t_alphanumeric0(input: Tokens) -> IResult<Tokens, Vec<Token>> {
+pub fn t_alphanumeric0(input: Tokens) -> ParseResult<Vec<Token>> {
   many0(alt((t_alpha,t_digit,)))(input)
 
 }
 
 // keywords 
 
-pub fn t_left_paren(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(&|tk| match tk.kind {
-    TokenKind::LeftParen => true,
-    _=> false,
-  }) ;
-  fxn(input.clone())
+pub fn t_left_paren(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::LeftParen);
+  fxn(input)
 }
 
-pub fn t_right_paren(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(&|tk| match tk.kind {
-    TokenKind::RightParen => true,
-    _=> false,
-  }) ;
-  fxn(input.clone())
+pub fn t_right_paren(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::RightParen);
+  fxn(input)
 }
 
 // Helper function to parse the curly brackets
-pub fn t_left_curly(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(&|tk| match tk.kind {
-    TokenKind::LeftCurly => true,
-    _=> false,
-  }) ;
-  fxn(input.clone())
+pub fn t_left_curly(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::LeftCurly);
+  fxn(input)
+}
+
+pub fn t_right_curly(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::RightCurly);
+  fxn(input)
 }
 
-pub fn t_right_curly(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(&|tk| match tk.kind {
-    TokenKind::RightCurly => true,
-    _=> false,
-  }) ;
-  fxn(input.clone())
+pub fn t_quote(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::Quote);
+  fxn(input)
 }
 
-pub fn t_quote(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::Quote => true,
-    _ => false,
-  });
-  fxn(input.clone())
+// Any token that may appear between the quotes of a string literal.
+pub fn t_string_char(input: Tokens) -> ParseResult<Token> {
+  match input.first() {
+    Some(token) if !matches!(token.kind, TokenKind::Quote | TokenKind::EOF) => split_tokens(input),
+    _ => Err(Err::Error(SyntaxError::new(&input, vec![]))),
+  }
 }
 
-pub fn t_slash(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::Slash => true,
-    _ => false,
-  });
-  fxn(input.clone())
+pub fn t_slash(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::Slash);
+  fxn(input)
 }
 
-pub fn t_comma(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(&|tk| match tk.kind {
-    TokenKind::Comma => true,
-    _=> false,
-  }) ;
-  fxn(input.clone())
+pub fn t_comma(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::Comma);
+  fxn(input)
 }
 
-pub fn t_semicolon(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(&|tk| match tk.kind {
-    TokenKind::Semicolon => true,
-    _=> false,
-  }) ;
-  fxn(input.clone())
+pub fn t_semicolon(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::Semicolon);
+  fxn(input)
 }
 
 
-pub fn t_let(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::Let => true,
-    _ => false,
-  });
-  fxn(input.clone())
+pub fn t_let(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::Let);
+  fxn(input)
 }
 
-pub fn t_fn(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::Fn => true,
-    _ => false,
-  });
-  fxn(input.clone())
+pub fn t_fn(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::Fn);
+  fxn(input)
 }
 
-pub fn t_return(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::Return => true,
-    _ => false,
-  });
-  fxn(input.clone())
+pub fn t_return(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::Return);
+  fxn(input)
 }
 
-pub fn t_whitespace(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::WhiteSpace => true,
-    _ => false,
-  });
-  fxn(input.clone())
+pub fn t_whitespace(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::WhiteSpace);
+  fxn(input)
 }
 
-pub fn t_plus(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::Plus => true,
-    _ => false,
-  });
-  fxn(input.clone())
+pub fn t_plus(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::Plus);
+  fxn(input)
 }
 
-pub fn t_dash(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::Dash => true,
-    _ => false,
-  });
-  fxn(input.clone())
+pub fn t_dash(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::Dash);
+  fxn(input)
 }
 
-pub fn t_equal(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::Equal => true,
-    _ => false,
-  });
-  fxn(input.clone())
+pub fn t_equal(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::Equal);
+  fxn(input)
 }
 
 
@@ -244,104 +257,199 @@ pub fn t_equal(input: Tokens) -> IResult<Tokens, Token> {
 
 //ADDED HELPER FUNCTIONS
 
-pub fn t_asterisk(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::Asterisk => true,
-    _ => false,
-  });
-  fxn(input.clone())
+pub fn t_asterisk(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::Asterisk);
+  fxn(input)
 }
 
-pub fn t_carot(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::Carot => true,
-    _ => false,
-  });
-  fxn(input.clone())
+pub fn t_carot(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::Carot);
+  fxn(input)
 }
 
-pub fn t_or(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::OrSign => true,
-    _ => false,
-  });
-  fxn(input.clone())
+pub fn t_or(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::OrSign);
+  fxn(input)
 }
 
-pub fn t_and(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::AndSign => true,
-    _ => false,
-  });
-  fxn(input.clone())
+pub fn t_and(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::AndSign);
+  fxn(input)
 }
 
-pub fn t_lessthan(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::LessThan => true,
-    _ => false,
-  });
-  fxn(input.clone())
+pub fn t_lessthan(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::LessThan);
+  fxn(input)
 }
 
-pub fn t_greaterthan(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::GreaterThan => true,
-    _ => false,
-  });
-  fxn(input.clone())
+pub fn t_greaterthan(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::GreaterThan);
+  fxn(input)
 }
 
-pub fn t_exclaimation(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::Exclaimation => true,
-    _ => false,
-  });
-  fxn(input.clone())
+pub fn t_exclaimation(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::Exclaimation);
+  fxn(input)
 }
 
-pub fn t_if(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::If => true,
-    _ => false,
-  });
-  fxn(input.clone())
+pub fn t_question(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::Question);
+  fxn(input)
 }
 
-pub fn t_else(input: Tokens) -> IResult<Tokens, Token> {
-  let fxn = check_token(& |tk| match tk.kind {
-    TokenKind::Else => true,
-    _ => false,
-  });
-  fxn(input.clone())
+pub fn t_try(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::Try);
+  fxn(input)
 }
 
+pub fn t_catch(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::Catch);
+  fxn(input)
+}
+
+pub fn t_throw(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::Throw);
+  fxn(input)
+}
 
+pub fn t_nil(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::Nil);
+  fxn(input)
+}
 
+// `??`, written with nothing between the two characters.
+pub fn t_double_question(input: Tokens) -> ParseResult<Token> {
+  let (rest, first) = t_question(input.clone())?;
+  let (rest, second) = t_question(rest)?;
+  if !first.touches(&second) {
+    return Err(Err::Error(SyntaxError::new(&input, vec![TokenKind::Question])));
+  }
+  Ok((rest, first))
+}
+
+// `?.`, written with nothing between the two characters.
+pub fn t_optional_dot(input: Tokens) -> ParseResult<Token> {
+  let (rest, first) = t_question(input.clone())?;
+  let (rest, second) = t_dot(rest)?;
+  if !first.touches(&second) {
+    return Err(Err::Error(SyntaxError::new(&input, vec![TokenKind::Dot])));
+  }
+  Ok((rest, first))
+}
+
+pub fn t_colon(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::Colon);
+  fxn(input)
+}
+
+pub fn t_const(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::Const);
+  fxn(input)
+}
+
+pub fn t_struct(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::Struct);
+  fxn(input)
+}
+
+pub fn t_enum(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::Enum);
+  fxn(input)
+}
+
+pub fn t_match(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::Match);
+  fxn(input)
+}
+
+// `=>`, written with nothing between the two characters.
+pub fn t_arrow(input: Tokens) -> ParseResult<Token> {
+  let (rest, first) = t_equal(input.clone())?;
+  let (rest, second) = t_greaterthan(rest)?;
+  if !first.touches(&second) {
+    return Err(Err::Error(SyntaxError::new(&input, vec![TokenKind::Equal])));
+  }
+  Ok((rest, first))
+}
+
+pub fn t_impl(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::Impl);
+  fxn(input)
+}
+
+pub fn t_dot(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::Dot);
+  fxn(input)
+}
+
+// `...`, written as three dots with nothing between them.
+pub fn t_ellipsis(input: Tokens) -> ParseResult<Token> {
+  let (rest, first) = t_dot(input.clone())?;
+  let (rest, second) = t_dot(rest)?;
+  let (rest, third) = t_dot(rest)?;
+  if !first.touches(&second) || !second.touches(&third) {
+    return Err(Err::Error(SyntaxError::new(&input, vec![TokenKind::Dot])));
+  }
+  Ok((rest, first))
+}
+
+pub fn t_left_bracket(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::LeftBracket);
+  fxn(input)
+}
+
+pub fn t_right_bracket(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::RightBracket);
+  fxn(input)
+}
+
+pub fn t_if(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::If);
+  fxn(input)
+}
+
+pub fn t_else(input: Tokens) -> ParseResult<Token> {
+  let fxn = check_token(TokenKind::Else);
+  fxn(input)
+}
+
+
+
+
+// Runs `parser` and names what it was in the middle of if it fails, so that
+// the error reads e.g. "expected `;` after statement".
+pub fn label<O>(context: &'static str, parser: impl Fn(Tokens) -> ParseResult<O>) -> impl Fn(Tokens) -> ParseResult<O> {
+  move |input: Tokens| {
+    parser(input.clone()).map_err(|err| err.map(|mut error| {
+      error.context = Some(context);
+      input.relabel_error(&error);
+      error
+    }))
+  }
+}
 
 //PARSER FUNCTIONS
 
-pub fn identifier(input: Tokens) -> IResult<Tokens, Node> {
-  let (input, first) = t_alpha(input)?;
-  let (input, rest) = t_alphanumeric0(input)?;
-  let mut identifier = first.lexeme;
-  for mut tk in rest {
-    identifier.append(&mut tk.lexeme);
+pub fn identifier(input: Tokens) -> ParseResult<Node> {
+  let (input, token) = t_alpha(input)?;
+  Ok((input,Node::Identifier{value: token.lexeme}))
+}
+
+// The lexer only makes a number token of digits that fit in an i32.
+pub fn number(input: Tokens) -> ParseResult<Node> {
+  let (rest, token) = t_digit(input.clone())?;
+  match String::from_utf8_lossy(&token.lexeme).parse::<i32>() {
+    Ok(value) => Ok((rest, Node::Number { value })),
+    Err(_) => Err(Err::Error(SyntaxError::new(&input, vec![]))),
   }
-  Ok((input,Node::Identifier{value: identifier}))
 }
 
-pub fn number(input: Tokens) -> IResult<Tokens, Node> {
-  let (input, digits) = many1(t_digit)(input)?;
-  let value: Vec<u8> = digits.iter()
-                             .flat_map(|token| token.lexeme.iter())
-                             .cloned()
-                             .collect();
-  let parsed_value: i32 = std::str::from_utf8(&value).unwrap().parse::<i32>().unwrap();
-  Ok((input, Node::Number { value: parsed_value }))
+pub fn nil(input: Tokens) -> ParseResult<Node> {
+  let (input, _) = t_nil(input)?;
+  Ok((input, Node::Nil{}))
 }
 
-pub fn boolean(input: Tokens) -> IResult<Tokens, Node> {
+pub fn boolean(input: Tokens) -> ParseResult<Node> {
   let (input, token) = alt((t_true, t_false))(input)?;
    let value = match token.kind {
       TokenKind::True => true,
@@ -351,18 +459,18 @@ pub fn boolean(input: Tokens) -> IResult<Tokens, Node> {
   Ok((input, Node::Bool { value }))
 }
 
-pub fn string(input: Tokens) -> IResult<Tokens, Node> {
- let (input, _) = t_quote(input)?;
-  let (input, string) = t_alphanumeric0(input)?;
- let (input, _) = t_quote(input)?;
- let value: Vec<u8> = string.into_iter()
-                               .map(|token| token.lexeme)
-                               .flatten()
-                               .collect();
- Ok((input, Node::String{ value: String::from_utf8(value).unwrap() }))
+pub fn string(input: Tokens) -> ParseResult<Node> {
+  let (input, _) = t_quote(input)?;
+  let (input, string) = many0(t_string_char)(input)?;
+  let (input, _) = t_quote(input)?;
+  let value: Vec<u8> = string.into_iter()
+                             .flat_map(|token| token.lexeme)
+                             .collect();
+  Ok((input, Node::String{ value: String::from_utf8(value).unwrap() }))
 }
 
-pub fn function_call(input: Tokens) -> IResult<Tokens, Node> {
+pub fn function_call(input: Tokens) -> ParseResult<Node> {
+  let location = location(&input);
   let (input, fxn_name) = identifier(input)?;
   let (input, _) = (t_left_paren)(input)?;
   let (input, args) = many0(arguments)(input)?;
@@ -376,174 +484,841 @@ pub fn function_call(input: Tokens) -> IResult<Tokens, Node> {
     Node::Identifier{value} => value,
     _ => unreachable!(),
   }; 
-  Ok((input, Node::FunctionCall{name, children: args}))
+  Ok((input, Node::FunctionCall{name, children: args, location}))
+}
+
+// A `{` in an expression starts a map if it is followed by `}` or by an
+// expression and a `:`. Otherwise it starts a block.
+pub fn value(input: Tokens) -> ParseResult<Node> {
+  alt((negation, lambda, if_statement, match_expression, try_catch, postfix, block_expression))(input)
+}
+
+// A value that can be indexed, followed by any number of indexes: `xs[0][1]`.
+pub fn postfix(input: Tokens) -> ParseResult<Node> {
+  let (mut input, mut result) = alt((parenthesized, list, map, struct_literal, function_call, number, nil, identifier, boolean, string))(input)?;
+  loop {
+    if let Ok((rest, index)) = index(input.clone()) {
+      result = Node::Index{children: vec![result, index]};
+      input = rest;
+    } else if let Ok((rest, (optional, name))) = field(input.clone()) {
+      let location = location(&input);
+      input = rest;
+      let arguments = match method_arguments(input.clone()) {
+        Ok((rest, arguments)) => {
+          input = rest;
+          Some(arguments)
+        },
+        Err(_) => None,
+      };
+      result = match (optional, arguments) {
+        (false, None) => Node::Field{name, children: vec![result]},
+        (false, Some(arguments)) => Node::MethodCall{name, children: vec![result, arguments], location},
+        (true, None) => Node::OptionalField{name, children: vec![result]},
+        (true, Some(arguments)) => Node::OptionalMethodCall{name, children: vec![result, arguments], location},
+      };
+    } else {
+      break;
+    }
+  }
+  Ok((input, result))
 }
 
-pub fn value(input: Tokens) -> IResult<Tokens, Node> {
-  alt((number, identifier, boolean))(input)
+// `.name`, or `?.name`, which is true in the result.
+fn field(input: Tokens) -> ParseResult<(bool, Vec<u8>)> {
+  let (input, optional) = alt((
+    |input| t_optional_dot(input).map(|(input, _)| (input, true)),
+    |input| t_dot(input).map(|(input, _)| (input, false)),
+  ))(input)?;
+  let (input, name) = label("after `.`", identifier)(input)?;
+  Ok((input, (optional, identifier_name(name))))
+}
+
+fn method_arguments(input: Tokens) -> ParseResult<Node> {
+  let (input, _) = t_left_paren(input)?;
+  let (input, arguments) = opt(arguments)(input)?;
+  let (input, _) = label("after arguments", t_right_paren)(input)?;
+  Ok((input, arguments.unwrap_or(Node::FunctionArguments{children: vec![]})))
+}
+
+fn identifier_name(node: Node) -> Vec<u8> {
+  match node {
+    Node::Identifier{value} => value,
+    _ => unreachable!(),
+  }
+}
+
+fn index(input: Tokens) -> ParseResult<Node> {
+  let (input, _) = t_left_bracket(input)?;
+  let (input, index) = ternary(input)?;
+  let (input, _) = label("after index", t_right_bracket)(input)?;
+  Ok((input, index))
+}
+
+pub fn struct_define(input: Tokens) -> ParseResult<Node> {
+  let (input, _) = t_struct(input)?;
+  let (input, name) = label("after `struct`", identifier)(input)?;
+  let (input, _) = label("after struct name", t_left_curly)(input)?;
+  let (input, fields) = opt(|input| {
+    let (input, first) = identifier(input)?;
+    let (input, mut others) = many0(|input| {
+      let (input, _) = t_comma(input)?;
+      identifier(input)
+    })(input)?;
+    others.insert(0, first);
+    Ok((input, others))
+  })(input)?;
+  let (input, _) = opt(t_comma)(input)?;
+  let (input, _) = label("after struct fields", t_right_curly)(input)?;
+  Ok((input, Node::StructDefine{name: identifier_name(name), children: fields.unwrap_or_default()}))
 }
 
-pub fn expression(input: Tokens) -> IResult<Tokens, Node> {
-   let (input, result) =  alt((conditional, boolean, math_expression, function_call, number, string,identifier))(input)?;
+// `Point { x: 1, y: 2 }`. Like a map, this is only read as a struct if the
+// `{` is followed by `}` or by a field name and a `:`.
+pub fn struct_literal(input: Tokens) -> ParseResult<Node> {
+  let (input, name) = identifier(input)?;
+  let (input, _) = t_left_curly(input)?;
+  let (input, fields) = opt(|input| {
+    let (input, first) = field_value(input)?;
+    let (input, mut others) = many0(|input| {
+      let (input, _) = t_comma(input)?;
+      field_value(input)
+    })(input)?;
+    others.insert(0, first);
+    Ok((input, others))
+  })(input)?;
+  let (input, _) = opt(t_comma)(input)?;
+  let (input, _) = t_right_curly(input)?;
+  Ok((input, Node::StructLiteral{name: identifier_name(name), children: fields.unwrap_or_default()}))
+}
+
+fn field_value(input: Tokens) -> ParseResult<Node> {
+  let (input, name) = identifier(input)?;
+  let (input, _) = t_colon(input)?;
+  let (input, value) = ternary(input)?;
+  Ok((input, Node::FieldValue{name: identifier_name(name), children: vec![value]}))
+}
+
+// `impl Point { fn dist(self) { ... } }` defines methods of a type.
+pub fn impl_define(input: Tokens) -> ParseResult<Node> {
+  let (input, _) = t_impl(input)?;
+  let (input, name) = label("after `impl`", identifier)(input)?;
+  let (input, _) = label("after type name", t_left_curly)(input)?;
+  let (input, methods) = many0(function_define)(input)?;
+  let (input, _) = label("after methods", t_right_curly)(input)?;
+  Ok((input, Node::ImplDefine{name: identifier_name(name), children: methods}))
+}
+
+pub fn enum_define(input: Tokens) -> ParseResult<Node> {
+  let (input, _) = t_enum(input)?;
+  let (input, name) = label("after `enum`", identifier)(input)?;
+  let (input, _) = label("after enum name", t_left_curly)(input)?;
+  let (input, variants) = opt(|input| {
+    let (input, first) = variant_define(input)?;
+    let (input, mut others) = many0(|input| {
+      let (input, _) = t_comma(input)?;
+      variant_define(input)
+    })(input)?;
+    others.insert(0, first);
+    Ok((input, others))
+  })(input)?;
+  let (input, _) = opt(t_comma)(input)?;
+  let (input, _) = label("after enum variants", t_right_curly)(input)?;
+  Ok((input, Node::EnumDefine{name: identifier_name(name), children: variants.unwrap_or_default()}))
+}
+
+// A variant without fields, like `Empty`, or with them, like `Rect(w, h)`.
+fn variant_define(input: Tokens) -> ParseResult<Node> {
+  let (input, name) = identifier(input)?;
+  let (input, fields) = opt(|input| {
+    let (input, _) = t_left_paren(input)?;
+    let (input, fields) = opt(|input| {
+      let (input, first) = identifier(input)?;
+      let (input, mut others) = many0(|input| {
+        let (input, _) = t_comma(input)?;
+        identifier(input)
+      })(input)?;
+      others.insert(0, first);
+      Ok((input, others))
+    })(input)?;
+    let (input, _) = opt(t_comma)(input)?;
+    let (input, _) = label("after variant fields", t_right_paren)(input)?;
+    Ok((input, fields.unwrap_or_default()))
+  })(input)?;
+  Ok((input, Node::VariantDefine{name: identifier_name(name), children: fields.unwrap_or_default()}))
+}
+
+pub fn match_expression(input: Tokens) -> ParseResult<Node> {
+  let (input, _) = t_match(input)?;
+  let (input, value) = match_value(input)?;
+  let (input, _) = label("before match arms", t_left_curly)(input)?;
+  let (input, mut arms) = many0(match_arm)(input)?;
+  let (input, _) = label("after match arms", t_right_curly)(input)?;
+  arms.insert(0, value);
+  Ok((input, Node::Match{children: arms}))
+}
+
+// The value of a `match`. A name followed by `{` isn't read as a struct
+// literal here, since the `{` starts the arms: the value is the shortest run of
+// tokens up to a `{` outside of brackets that is a whole expression. So
+// `match x { }` matches `x`, and a struct literal has to be in brackets.
+fn match_value(input: Tokens) -> ParseResult<Node> {
+  let mut depth = 0;
+  let mut in_string = false;
+  for (position, token) in input.iter().enumerate() {
+    match token.kind {
+      TokenKind::Quote => in_string = !in_string,
+      _ if in_string => (),
+      TokenKind::LeftCurly if depth == 0 => {
+        if let Ok((rest, value)) = ternary(input.take(position)) {
+          if rest.is_empty() {
+            return Ok((input.skip(position), value));
+          }
+        }
+        depth += 1;
+      },
+      TokenKind::LeftCurly | TokenKind::LeftParen | TokenKind::LeftBracket => depth += 1,
+      TokenKind::RightCurly | TokenKind::RightParen | TokenKind::RightBracket if depth == 0 => break,
+      TokenKind::RightCurly | TokenKind::RightParen | TokenKind::RightBracket => depth -= 1,
+      _ => (),
+    }
+  }
+  ternary(input)
+}
+
+fn match_arm(input: Tokens) -> ParseResult<Node> {
+  let (input, pattern) = pattern(input)?;
+  let (input, _) = label("after pattern", t_arrow)(input)?;
+  let (input, body) = expression(input)?;
+  let (input, _) = opt(t_comma)(input)?;
+  Ok((input, Node::MatchArm{children: vec![pattern, body]}))
+}
+
+// A pattern is `_`, a literal, a variant with patterns for its fields, or a
+// name. A name matches the variant without fields it names, if there is
+// one, and otherwise matches anything and binds it.
+pub fn pattern(input: Tokens) -> ParseResult<Node> {
+  alt((variant_pattern, negative_number, number, string, boolean, nil, name_pattern))(input)
+}
+
+fn variant_pattern(input: Tokens) -> ParseResult<Node> {
+  let (input, name) = identifier(input)?;
+  let (input, _) = t_left_paren(input)?;
+  let (input, patterns) = opt(|input| {
+    let (input, first) = pattern(input)?;
+    let (input, mut others) = many0(|input| {
+      let (input, _) = t_comma(input)?;
+      pattern(input)
+    })(input)?;
+    others.insert(0, first);
+    Ok((input, others))
+  })(input)?;
+  let (input, _) = opt(t_comma)(input)?;
+  let (input, _) = label("after patterns", t_right_paren)(input)?;
+  Ok((input, Node::VariantPattern{name: identifier_name(name), children: patterns.unwrap_or_default()}))
+}
+
+fn negative_number(input: Tokens) -> ParseResult<Node> {
+  let (input, _) = t_dash(input)?;
+  let (input, number) = number(input)?;
+  match number {
+    Node::Number{value} => Ok((input, Node::Number{value: -value})),
+    _ => unreachable!(),
+  }
+}
+
+fn name_pattern(input: Tokens) -> ParseResult<Node> {
+  let (input, name) = identifier(input)?;
+  match name {
+    Node::Identifier{value} if value == b"_" => Ok((input, Node::Wildcard{})),
+    name => Ok((input, name)),
+  }
+}
+
+pub fn map(input: Tokens) -> ParseResult<Node> {
+  let (input, _) = t_left_curly(input)?;
+  let (input, entries) = opt(|input| {
+    let (input, first) = map_entry(input)?;
+    let (input, mut others) = many0(|input| {
+      let (input, _) = t_comma(input)?;
+      map_entry(input)
+    })(input)?;
+    others.insert(0, first);
+    Ok((input, others))
+  })(input)?;
+  let (input, _) = opt(t_comma)(input)?;
+  let (input, _) = t_right_curly(input)?;
+  Ok((input, Node::Map{children: entries.unwrap_or_default()}))
+}
+
+fn map_entry(input: Tokens) -> ParseResult<Node> {
+  let (input, key) = ternary(input)?;
+  let (input, _) = t_colon(input)?;
+  let (input, value) = ternary(input)?;
+  Ok((input, Node::MapEntry{children: vec![key, value]}))
+}
+
+pub fn list(input: Tokens) -> ParseResult<Node> {
+  let (input, _) = t_left_bracket(input)?;
+  let (input, elements) = opt(|input| {
+    let (input, first) = ternary(input)?;
+    let (input, mut others) = many0(|input| {
+      let (input, _) = t_comma(input)?;
+      ternary(input)
+    })(input)?;
+    others.insert(0, first);
+    Ok((input, others))
+  })(input)?;
+  let (input, _) = opt(t_comma)(input)?;
+  let (input, _) = label("after list elements", t_right_bracket)(input)?;
+  Ok((input, Node::List{children: elements.unwrap_or_default()}))
+}
+
+pub fn negation(input: Tokens) -> ParseResult<Node> {
+  let (input, _) = t_dash(input)?;
+  let (input, value) = value(input)?;
+  Ok((input, Node::Negation{children: vec![value]}))
+}
+
+pub fn parenthesized(input: Tokens) -> ParseResult<Node> {
+  let (input, _) = t_left_paren(input)?;
+  let (input, result) = ternary(input)?;
+  let (input, _) = t_right_paren(input)?;
+  Ok((input, result))
+}
+
+// Expressions are parsed one precedence level at a time, from loosest to
+// tightest binding:
+//   ternary          c ? a : b     (right associative)
+//   conditional      ||
+//   and_expression   &&
+//   comparison       == != < > <= >=  (not associative)
+//   math_expression  + -
+//   value            unary minus, literals, calls, blocks, `(...)` and
+//                    indexing
+pub fn expression(input: Tokens) -> ParseResult<Node> {
+   let (input, result) = ternary(input)?;
    Ok((input, Node::Expression{children: vec! [result]}))
 }
 
-pub fn statement(input: Tokens) -> IResult<Tokens, Node> {
-  let (input, result) = alt((variable_define, expression, function_return, if_statement))(input)?;
-  let (input, _) = (t_semicolon)(input)?;
+pub fn statement(input: Tokens) -> ParseResult<Node> {
+  alt((block_statement, simple_statement))(input)
+}
+
+// Statements that end in a block don't need a `;` after the closing brace,
+// though one is still allowed.
+pub fn block_statement(input: Tokens) -> ParseResult<Node> {
+  let (input, result) = block_like(input)?;
+  let (input, _) = opt(t_semicolon)(input)?;
   Ok((input, result))
 }
 
-pub fn function_return(input: Tokens) -> IResult<Tokens, Node> {
+fn block_like(input: Tokens) -> ParseResult<Node> {
+  alt((if_statement, match_expression, try_catch, block_expression))(input)
+}
+
+pub fn simple_statement(input: Tokens) -> ParseResult<Node> {
+  let (input, result) = alt((variable_define, assignment, expression, function_return, throw))(input)?;
+  let (input, _) = label("after statement", t_semicolon)(input)?;
+  Ok((input, result))
+}
+
+pub fn block(input: Tokens) -> ParseResult<Vec<Node>> {
+  let (input, _) = t_left_curly(input)?;
+  let (input, statements) = block_contents(input)?;
+  let (input, _) = label("after block", t_right_curly)(input)?;
+  Ok((input, statements))
+}
+
+// The statements of a block, which may end in an expression without a `;`
+// that becomes its tail. As in Rust, an `if`, `match`, `try` or block at the
+// end of a block without a `;` is its tail too.
+pub fn block_contents(input: Tokens) -> ParseResult<Vec<Node>> {
+  let mut input = input;
+  let mut statements = vec![];
+  loop {
+    if let Ok((rest, result)) = block_like(input.clone()) {
+      if t_right_curly(rest.clone()).is_ok() {
+        statements.push(Node::Tail{children: vec![result]});
+        return Ok((rest, statements));
+      }
+      let (rest, _) = opt(t_semicolon)(rest)?;
+      statements.push(result);
+      input = rest;
+    } else if let Ok((rest, result)) = simple_statement(input.clone()) {
+      statements.push(result);
+      input = rest;
+    } else {
+      break;
+    }
+  }
+  let (input, tail) = opt(expression)(input)?;
+  statements.extend(tail.map(|tail| Node::Tail{children: vec![tail]}));
+  Ok((input, statements))
+}
+
+pub fn block_expression(input: Tokens) -> ParseResult<Node> {
+  let (input, statements) = block(input)?;
+  Ok((input, Node::Block{children: statements}))
+}
+
+// `try { ... } catch (e) { ... }` runs the second block with the error if
+// the first one fails.
+pub fn try_catch(input: Tokens) -> ParseResult<Node> {
+  let (input, _) = t_try(input)?;
+  let (input, _) = label("after `try`", t_left_curly)(input)?;
+  let (input, statements) = block_contents(input)?;
+  let (input, _) = label("after `try` block", t_right_curly)(input)?;
+  let (input, _) = label("after `try` block", t_catch)(input)?;
+  let (input, _) = label("after `catch`", t_left_paren)(input)?;
+  let (input, error) = binding(input)?;
+  let (input, _) = label("after error name", t_right_paren)(input)?;
+  let (input, handler) = block(input)?;
+  Ok((input, Node::TryCatch{children: vec![Node::Block{children: statements}, error, Node::Block{children: handler}]}))
+}
+
+pub fn throw(input: Tokens) -> ParseResult<Node> {
+  let (input, _) = t_throw(input)?;
+  let (input, value) = expression(input)?;
+  Ok((input, Node::Throw{children: vec![value]}))
+}
+
+pub fn function_return(input: Tokens) -> ParseResult<Node> {
   let (input, _) = t_return(input)?;
-  let (input, result) = alt((function_call,expression, identifier))(input)?;
+  let (input, result) = expression(input)?;
   Ok((input, Node::FunctionReturn{children: vec! [result]}))
 }
 
-pub fn variable_define(input: Tokens) -> IResult<Tokens, Node> {
+// Assigns to an element of a variable: `xs[0] = 1`. Variables themselves
+// can only be defined with `let`.
+pub fn assignment(input: Tokens) -> ParseResult<Node> {
+  let (input, target) = postfix(input)?;
+  if !matches!(target, Node::Index{..} | Node::Field{..}) {
+    return Err(Err::Error(SyntaxError::new(&input, vec![TokenKind::LeftBracket, TokenKind::Dot])));
+  }
+  let (input, _) = t_equal(input)?;
+  let (input, value) = expression(input)?;
+  Ok((input, Node::Assignment{children: vec![target, value]}))
+}
+
+pub fn variable_define(input: Tokens) -> ParseResult<Node> {
   let (input, _) = t_let(input)?;
-  let (input, variable) = identifier(input)?;
-  let (input, _) = (t_equal)(input)?;
+  let (input, variable) = label("after `let`", binding)(input)?;
+  let (input, _) = label("after variable name", t_equal)(input)?;
   let (input, expression) = expression(input)?;
   Ok((input, Node::VariableDefine{children: vec![variable,expression]}))
 }
 
-pub fn arguments(input: Tokens) -> IResult<Tokens, Node> {
-  let (input, arg) = expression(input)?;
+// Constants can only be defined at the top level of a program.
+pub fn const_define(input: Tokens) -> ParseResult<Node> {
+  let (input, _) = t_const(input)?;
+  let (input, constant) = label("after `const`", identifier)(input)?;
+  let (input, _) = label("after constant name", t_equal)(input)?;
+  let (input, expression) = expression(input)?;
+  let (input, _) = label("after statement", t_semicolon)(input)?;
+  Ok((input, Node::ConstDefine{children: vec![constant,expression]}))
+}
+
+pub fn arguments(input: Tokens) -> ParseResult<Node> {
+  let (input, arg) = argument(input)?;
   let (input, mut others) = many0(other_arg) (input)?;
   let mut args = vec! [arg];
   args.append (&mut others) ;
   Ok((input, Node::FunctionArguments{children: args}))
 }
 
-pub fn other_arg(input: Tokens) -> IResult<Tokens, Node> {
+pub fn other_arg(input: Tokens) -> ParseResult<Node> {
   let (input, _) = t_comma(input)?;
-  expression(input)
+  argument(input)
 }
 
-pub fn function_define(input: Tokens) -> IResult<Tokens, Node> {
+pub fn argument(input: Tokens) -> ParseResult<Node> {
+  alt((named_argument, spread_argument, expression))(input)
+}
+
+// A list spread into positional arguments: `...values`.
+pub fn spread_argument(input: Tokens) -> ParseResult<Node> {
+  let (input, _) = t_ellipsis(input)?;
+  let (input, value) = expression(input)?;
+  Ok((input, Node::SpreadArgument{children: vec![value]}))
+}
+
+// An argument passed by the name of its parameter: `port: 5432`.
+pub fn named_argument(input: Tokens) -> ParseResult<Node> {
+  let (input, name) = identifier(input)?;
+  let (input, _) = t_colon(input)?;
+  let (input, value) = expression(input)?;
+  let name = match name {
+    Node::Identifier{value} => value,
+    _ => unreachable!(),
+  };
+  Ok((input, Node::NamedArgument{name, children: vec![value]}))
+}
+
+pub fn function_define(input: Tokens) -> ParseResult<Node> {
   let (input, _) = t_fn(input)?;
-  let (input, fxn_name) = identifier(input)?;
+  let (input, fxn_name) = label("after `fn`", identifier)(input)?;
   let name = match fxn_name {
     Node::Identifier{value} => value,
     _ => unreachable!(),
   };
-  let (input, _) = t_left_paren(input)?;
-  let (input, args) = many0(arguments)(input)?;
-  let (input, _) = t_right_paren(input)?;
-  let (input, _) = t_left_curly(input)?;
-  let (input, statements) = many1(statement)(input)?;
-  let (input, _) = t_right_curly(input)?;
+  let (input, _) = label("after function name", t_left_paren)(input)?;
+  let (input, parameters) = opt(parameter_list)(input)?;
+  let (input, _) = label("after parameters", t_right_paren)(input)?;
+  let (input, _) = label("before function body", t_left_curly)(input)?;
+  let (input, statements) = block_contents(input)?;
+  let (input, _) = label("after function body", t_right_curly)(input)?;
   let fxn_statements = Node::FunctionStatements{children: statements};
-  let fxn_arguments = if args.is_empty() {
-    Node::FunctionArguments{children: vec![]}
-  } else {
-    args[0].clone()
-  };
+  let fxn_arguments = parameters.unwrap_or(Node::FunctionArguments{children: vec![]});
   Ok((input, Node::FunctionDefine{name, children: vec![fxn_arguments,fxn_statements] }))
 }
 
-pub fn comment(input: Tokens) -> IResult<Tokens, Node> {
-  let mut comment_text = Vec::new();
-  let (input, _) = t_slash(input)?;
-  let (input, _) = t_slash(input)?;
-  let (input, alpha_tokens) = many0(t_alpha)(input)?;
-  for token in alpha_tokens {
-    comment_text.extend_from_slice(&token.lexeme);
+// An anonymous function, either `|a, b| a + b` with a single expression as
+// its body, or `fn(a, b) { return a + b; }`. Like a named function it has
+// arguments and statements as its children.
+pub fn lambda(input: Tokens) -> ParseResult<Node> {
+  alt((bar_lambda, fn_lambda))(input)
+}
+
+fn bar_lambda(input: Tokens) -> ParseResult<Node> {
+  let (input, _) = t_or(input)?;
+  let (input, parameters) = opt(parameter_list)(input)?;
+  let (input, _) = label("after lambda parameters", t_or)(input)?;
+  let (input, body) = expression(input)?;
+  let fxn_arguments = parameters.unwrap_or(Node::FunctionArguments{children: vec![]});
+  let fxn_statements = Node::FunctionStatements{children: vec![Node::Tail{children: vec![body]}]};
+  Ok((input, Node::Lambda{children: vec![fxn_arguments, fxn_statements]}))
+}
+
+// The parameters of a function, of which the last may be a rest parameter.
+pub fn parameter_list(input: Tokens) -> ParseResult<Node> {
+  let (mut input, first) = alt((rest_parameter, parameter))(input)?;
+  let mut parameters = vec![first];
+  while !matches!(parameters.last(), Some(Node::RestParameter{..})) {
+    let Ok((rest, _)) = t_comma(input.clone()) else { break };
+    let (rest, parameter) = alt((rest_parameter, parameter))(rest)?;
+    parameters.push(parameter);
+    input = rest;
+  }
+  Ok((input, Node::FunctionArguments{children: parameters}))
+}
+
+// A parameter that collects any extra positional arguments into a list:
+// `...rest`.
+pub fn rest_parameter(input: Tokens) -> ParseResult<Node> {
+  let (input, _) = t_ellipsis(input)?;
+  let (input, name) = identifier(input)?;
+  Ok((input, Node::RestParameter{children: vec![name]}))
+}
+
+// A parameter is a name or a pattern, optionally with a default value:
+// `port = 8080`.
+pub fn parameter(input: Tokens) -> ParseResult<Node> {
+  let (input, name) = binding(input)?;
+  let (input, default) = opt(|input| {
+    let (input, _) = t_equal(input)?;
+    expression(input)
+  })(input)?;
+  match default {
+    Some(default) => Ok((input, Node::DefaultParameter{children: vec![name, default]})),
+    None => Ok((input, name)),
+  }
+}
+
+// What a `let` or a parameter binds a value to: a name, `_`, or a pattern
+// that takes the value apart, like `[a, b]` or `Point { x, y }`.
+pub fn binding(input: Tokens) -> ParseResult<Node> {
+  alt((list_pattern, struct_pattern, name_pattern))(input)
+}
+
+// `[first, second]`, or `[first, ...rest]` to take any number of elements.
+fn list_pattern(input: Tokens) -> ParseResult<Node> {
+  let (mut input, _) = t_left_bracket(input)?;
+  let mut elements = Vec::new();
+  while let Ok((rest, element)) = alt((rest_parameter, binding))(input.clone()) {
+    let last = matches!(element, Node::RestParameter{..});
+    elements.push(element);
+    input = rest;
+    let Ok((rest, _)) = t_comma(input.clone()) else { break };
+    input = rest;
+    if last {
+      break;
+    }
+  }
+  let (input, _) = label("after list pattern", t_right_bracket)(input)?;
+  Ok((input, Node::ListPattern{children: elements}))
+}
+
+// `Point { x, y: other }` binds `x` to the field `x` and `other` to `y`.
+fn struct_pattern(input: Tokens) -> ParseResult<Node> {
+  let (input, name) = identifier(input)?;
+  let (input, _) = t_left_curly(input)?;
+  let (input, fields) = opt(|input| {
+    let (input, first) = field_pattern(input)?;
+    let (input, mut others) = many0(|input| {
+      let (input, _) = t_comma(input)?;
+      field_pattern(input)
+    })(input)?;
+    others.insert(0, first);
+    Ok((input, others))
+  })(input)?;
+  let (input, _) = opt(t_comma)(input)?;
+  let (input, _) = label("after struct pattern", t_right_curly)(input)?;
+  Ok((input, Node::StructPattern{name: identifier_name(name), children: fields.unwrap_or_default()}))
+}
+
+fn field_pattern(input: Tokens) -> ParseResult<Node> {
+  let (input, field) = identifier(input)?;
+  let (input, pattern) = opt(|input| {
+    let (input, _) = t_colon(input)?;
+    binding(input)
+  })(input)?;
+  let pattern = pattern.unwrap_or_else(|| field.clone());
+  Ok((input, Node::FieldPattern{name: identifier_name(field), children: vec![pattern]}))
+}
+
+fn fn_lambda(input: Tokens) -> ParseResult<Node> {
+  let (input, _) = t_fn(input)?;
+  let (input, _) = t_left_paren(input)?;
+  let (input, parameters) = opt(parameter_list)(input)?;
+  let (input, _) = label("after parameters", t_right_paren)(input)?;
+  let (input, _) = label("before function body", t_left_curly)(input)?;
+  let (input, statements) = block_contents(input)?;
+  let (input, _) = label("after function body", t_right_curly)(input)?;
+  let fxn_statements = Node::FunctionStatements{children: statements};
+  let fxn_arguments = parameters.unwrap_or(Node::FunctionArguments{children: vec![]});
+  Ok((input, Node::Lambda{children: vec![fxn_arguments, fxn_statements]}))
+}
+
+pub fn program_item(input: Tokens) -> ParseResult<Node> {
+  alt((function_define,const_define,struct_define,enum_define,impl_define,statement,expression,string,boolean,number))(input)
+}
+
+// Picks whichever is further along: `error`, or the furthest one recorded
+// while parsing `input`, which may have been swallowed by a `many0`.
+fn furthest_error(input: &Tokens, error: SyntaxError) -> SyntaxError {
+  match input.take_furthest_error() {
+    Some(furthest) if !error.is_further_than(&furthest) => nom::error::ParseError::or(furthest, error),
+    _ => error,
   }
-  Ok((input, Node::Comment{ value: comment_text }))
 }
 
-pub fn program(input: Tokens) -> IResult<Tokens, Node> {
-  let (input, result) = many1(alt((function_define,expression,statement,string,boolean,number)))(input)?;
-  Ok((input, Node::Program{ children: result }))
+pub fn program(input: Tokens) -> ParseResult<Node> {
+  let (rest, result) = many1(program_item)(input.clone()).map_err(|err| err.map(|error| furthest_error(&input, error)))?;
+  // `many1` stops quietly at the first item it cannot parse, so try that item
+  // again to report why.
+  if !rest.is_done() {
+    if let Err(err) = program_item(rest.clone()) {
+      return Err(err.map(|error| furthest_error(&rest, error)));
+    }
+  }
+  Ok((rest, Node::Program{ children: result }))
+}
+
+// Parses a whole program like `program`, but keeps going after a syntax error
+// so that every error in the input is reported. After an error the parser
+// skips ahead to the end of the statement or block it was in, or to the next
+// function definition, and carries on from there.
+pub fn parse(input: Tokens) -> Result<Node, Vec<SyntaxError>> {
+  let mut input = input;
+  let mut children = vec![];
+  let mut errors = vec![];
+  // Blocks opened by code that was skipped during recovery. Their closing
+  // braces are expected later on and are skipped too.
+  let mut depth = 0;
+  while !input.is_done() {
+    if depth > 0 {
+      if let Ok((rest, _)) = t_right_curly(input.clone()) {
+        input = rest;
+        depth -= 1;
+        continue;
+      }
+    }
+    input.take_furthest_error();
+    match program_item(input.clone()) {
+      Ok((rest, node)) => {
+        children.push(node);
+        input = rest;
+      }
+      Err(Err::Error(error)) | Err(Err::Failure(error)) => {
+        let error = furthest_error(&input, error);
+        (input, depth) = recover(input, &error, depth);
+        errors.push(error);
+      }
+      Err(Err::Incomplete(_)) => unreachable!(),
+    }
+  }
+  if errors.is_empty() {
+    Ok(Node::Program{ children })
+  } else {
+    Err(errors)
+  }
+}
+
+// Skips the tokens of an item that failed to parse, up to and including the
+// first `;` or `}` after the error, or up to the next `fn`. A `}` closing braces
+// opened in the skipped tokens, as in `let m = {"a" 1};`, ends the statement
+// they were in, so a `;` right after it is skipped too. Returns the rest of the
+// input and the number of blocks left open.
+fn recover(input: Tokens, error: &SyntaxError, mut depth: usize) -> (Tokens, usize) {
+  let mut input = input;
+  let mut skipped = false;
+  let mut opened: usize = 0;
+  while let Some(token) = input.first() {
+    let past_error = (token.start_line, token.start_col) >= (error.line, error.col);
+    match token.kind {
+      TokenKind::EOF => break,
+      TokenKind::Fn if past_error && skipped => return (input, 0),
+      TokenKind::Semicolon if past_error => return (input.skip(1), depth),
+      TokenKind::RightCurly if past_error => {
+        let rest = input.skip(1);
+        let semicolon = matches!(rest.first(), Some(token) if token.kind == TokenKind::Semicolon);
+        return (if opened > 0 && semicolon { rest.skip(1) } else { rest }, depth.saturating_sub(1));
+      },
+      TokenKind::LeftCurly => {
+        depth += 1;
+        opened += 1;
+      },
+      TokenKind::RightCurly => {
+        depth = depth.saturating_sub(1);
+        opened = opened.saturating_sub(1);
+      },
+      _ => (),
+    }
+    input = input.skip(1);
+    skipped = true;
+  }
+  (input, depth)
 }
 
 //added to parser.rs for final
 
-pub fn math_expression(input: Tokens) -> IResult<Tokens, Node> {
-  let (input, leftside) = value(input)?;
-  let (input, operator) = alt((t_plus, t_dash))(input)?;
-  let (input, rightside) = value(input)?;
-  let name = match operator.kind {
-    TokenKind::Plus => b"add",
-    TokenKind::Dash => b"sub",
-    _ => unreachable!(),
-  };
-  Ok((input, Node::MathExpression{name: name.to_vec(), children: vec![leftside, rightside] }))
+pub fn ternary(input: Tokens) -> ParseResult<Node> {
+  let (input, condition) = coalesce(input)?;
+  let (input, question) = opt(t_question)(input)?;
+  if question.is_none() {
+    return Ok((input, condition));
+  }
+  let (input, if_true) = ternary(input)?;
+  let (input, _) = label("in conditional expression", t_colon)(input)?;
+  let (input, if_false) = ternary(input)?;
+  Ok((input, Node::TernaryExpression{children: vec![condition, if_true, if_false]}))
 }
 
-pub fn conditional(input: Tokens) -> IResult<Tokens, Node> {
-  let (input, leftside) = value(input)?;
+// `a ?? b` is `a`, unless that is nil, in which case it is `b`.
+pub fn coalesce(input: Tokens) -> ParseResult<Node> {
+  let (mut input, mut leftside) = conditional(input)?;
+  while let Ok((rest, _)) = t_double_question(input.clone()) {
+    let (rest, rightside) = conditional(rest)?;
+    leftside = Node::Coalesce{children: vec![leftside, rightside]};
+    input = rest;
+  }
+  Ok((input, leftside))
+}
+
+pub fn conditional(input: Tokens) -> ParseResult<Node> {
+  let (mut input, mut leftside) = and_expression(input)?;
+  while let Ok((rest, _)) = or_operator(input.clone()) {
+    let (rest, rightside) = and_expression(rest)?;
+    leftside = Node::ConditionalExpression{children: vec![leftside, Node::Or{}, rightside]};
+    input = rest;
+  }
+  Ok((input, leftside))
+}
+
+pub fn and_expression(input: Tokens) -> ParseResult<Node> {
+  let (mut input, mut leftside) = comparison(input)?;
+  while let Ok((rest, _)) = and_operator(input.clone()) {
+    let (rest, rightside) = comparison(rest)?;
+    leftside = Node::ConditionalExpression{children: vec![leftside, Node::And{}, rightside]};
+    input = rest;
+  }
+  Ok((input, leftside))
+}
+
+pub fn comparison(input: Tokens) -> ParseResult<Node> {
+  let (input, leftside) = math_expression(input)?;
+  let (input, operator) = opt(comparison_operator)(input)?;
+  match operator {
+    Some(operator) => {
+      let (input, rightside) = math_expression(input)?;
+      Ok((input, Node::ConditionalExpression{children: vec![leftside, operator, rightside]}))
+    },
+    None => Ok((input, leftside)),
+  }
+}
+
+pub fn math_expression(input: Tokens) -> ParseResult<Node> {
+  let (mut input, mut leftside) = value(input)?;
+  while let Ok((rest, operator)) = alt((t_plus, t_dash))(input.clone()) {
+    let (rest, rightside) = value(rest)?;
+    let name = match operator.kind {
+      TokenKind::Plus => b"add",
+      TokenKind::Dash => b"sub",
+      _ => unreachable!(),
+    };
+    leftside = Node::MathExpression{name: name.to_vec(), children: vec![leftside, rightside] };
+    input = rest;
+  }
+  Ok((input, leftside))
+}
+
+fn or_operator(input: Tokens) -> ParseResult<Token> {
+  let (input, _) = t_or(input)?;
+  t_or(input)
+}
+
+fn and_operator(input: Tokens) -> ParseResult<Token> {
+  let (input, _) = t_and(input)?;
+  t_and(input)
+}
+
+pub fn comparison_operator(input: Tokens) -> ParseResult<Node> {
   let (input, first_operator) = alt((
-    t_and,
-    t_or,
     t_lessthan,
     t_greaterthan,
     t_equal,
     t_exclaimation,
   ))(input)?;
-  let (input, second_operator) = opt(alt((t_equal, t_and, t_or)))(input)?;
-  let (input, rightside) = value(input)?;
-  
-  let mut vec = Vec::new();
-  vec.push(leftside);
-
-  match (first_operator.kind, second_operator.map(|op| op.kind)) {
-    (TokenKind::Equal, Some(TokenKind::Equal)) => vec.push(Node::EqualTo {}),
-    (TokenKind::Exclaimation, Some(TokenKind::Equal)) => vec.push(Node::NotEqualTo {}),
-    (TokenKind::LessThan, Some(TokenKind::Equal)) => vec.push(Node::LessThanOrEqualTo {}),
-    (TokenKind::LessThan, None) => vec.push(Node::LessThan {}),
-    (TokenKind::GreaterThan, Some(TokenKind::Equal)) => vec.push(Node::GreaterThanOrEqualTo {}),
-    (TokenKind::GreaterThan, None) => vec.push(Node::GreaterThan {}),
-    (TokenKind::AndSign, Some(TokenKind::AndSign)) => vec.push(Node::And{}),
-    (TokenKind::OrSign, Some(TokenKind::OrSign)) => vec.push(Node::Or{}),
-    _ => unreachable!()
-  }
-  vec.push(rightside);
-
-  Ok((input, Node::ConditionalExpression{children: vec}))
+  let (rest, second_operator) = opt(t_equal)(input.clone())?;
+
+  let operator = match (first_operator.kind, second_operator.map(|op| op.kind)) {
+    (TokenKind::Equal, Some(TokenKind::Equal)) => Node::EqualTo {},
+    (TokenKind::Exclaimation, Some(TokenKind::Equal)) => Node::NotEqualTo {},
+    (TokenKind::LessThan, Some(TokenKind::Equal)) => Node::LessThanOrEqualTo {},
+    (TokenKind::LessThan, None) => Node::LessThan {},
+    (TokenKind::GreaterThan, Some(TokenKind::Equal)) => Node::GreaterThanOrEqualTo {},
+    (TokenKind::GreaterThan, None) => Node::GreaterThan {},
+    _ => return Err(Err::Error(SyntaxError::new(&input, vec![TokenKind::Equal]))),
+  };
+  Ok((rest, operator))
 }
 
-pub fn if_statement(input: Tokens) -> IResult<Tokens, Node> {
+pub fn if_statement(input: Tokens) -> ParseResult<Node> {
   let (input, _) = t_if(input)?;
-  let (input, _) = t_left_paren(input)?;
-  let (input, condition) = conditional(input)?;
-  let (input, _) = t_right_paren(input)?;
-  let (input, _) = t_left_curly(input)?;
-  let (input, if_statements) = many1(statement)(input)?;
-  let (input, _) = t_right_curly(input)?;
-  let (input, _) = t_else(input)?;
-  let (input, mut else_if) = many0(else_if)(input)?;
-  let (input, else_stmt) = else_statement(input)?;
-  
-  let mut vec = Vec::new();
-  let else_if = if else_if.is_empty(){
-
-  }else{
-    vec.extend_from_slice(&else_if)
-  };
-  vec.push(else_stmt);
+  let (input, _) = label("after `if`", t_left_paren)(input)?;
+  let (input, condition) = ternary(input)?;
+  let (input, _) = label("after condition", t_right_paren)(input)?;
+  let (input, if_statements) = block(input)?;
+  let (input, mut vec) = many0(else_if)(input)?;
+  let (input, else_stmt) = opt(else_statement)(input)?;
+  vec.extend(else_stmt);
 
   Ok((input, Node::IfStatement{case: vec![condition], statements: if_statements, else_statements: vec}))
 }
 
-pub fn else_if(input: Tokens) -> IResult<Tokens, Node> {
-  let (input, _) = t_if(input)?;
-  let (input, _) = t_left_paren(input)?;
-  let (input, condition) = conditional(input)?;
-  let (input, _) = t_right_paren(input)?;
-  let (input, _) = t_left_curly(input)?;
-  let (input, if_statements) = many1(statement)(input)?;
-  let (input, _) = t_right_curly(input)?;
+pub fn else_if(input: Tokens) -> ParseResult<Node> {
   let (input, _) = t_else(input)?;
+  let (input, _) = t_if(input)?;
+  let (input, _) = label("after `if`", t_left_paren)(input)?;
+  let (input, condition) = ternary(input)?;
+  let (input, _) = label("after condition", t_right_paren)(input)?;
+  let (input, if_statements) = block(input)?;
   Ok((input, Node::ElseIfStatement{case: vec![condition], statements: if_statements}))
 }
 
-pub fn else_statement(input: Tokens) -> IResult<Tokens, Node> {
-  let (input, _) = t_left_curly(input)?;
-  let (input, else_statements) = many1(statement)(input)?;
-  let (input, _) = t_right_curly(input)?;
+pub fn else_statement(input: Tokens) -> ParseResult<Node> {
+  let (input, _) = t_else(input)?;
+  let (input, else_statements) = label("after `else`", block)(input)?;
   Ok((input, Node::ElseStatement{ statements: else_statements}))
 }
 
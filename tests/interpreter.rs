@@ -3,7 +3,6 @@ extern crate nom;
 use std::io::Write;
 
 use asalang::*;
-use nom::IResult;
 
 macro_rules! test_fragment {
   ($func:ident, $test:tt, $expected:expr) => (
@@ -15,7 +14,7 @@ macro_rules! test_fragment {
           assert_eq!(tokens.is_done(), true); // Check that input token stream is fully parsed
           let mut interpreter = Interpreter::new();
           let result = interpreter.exec(&tree);
-          std::io::stdout().flush();
+          std::io::stdout().flush().unwrap();
           assert_eq!(result, $expected);
           Ok(())
         },
@@ -34,7 +33,7 @@ macro_rules! test_program {
         Ok((tokens, tree)) => {
           assert_eq!(tokens.is_done(), true); // Check that input token stream is fully parsed
           let mut interpreter = Interpreter::new();
-          let compile_result = interpreter.exec(&tree)?;
+          interpreter.exec(&tree)?;
           let main_result = interpreter.start_main(vec![]);
           assert_eq!(main_result, $expected);
           Ok(())
@@ -45,9 +44,20 @@ macro_rules! test_program {
   )
 }
 
+macro_rules! test_script {
+  ($func:ident, $test:tt, $expected:expr) => (
+    #[test]
+    fn $func() {
+      let tree = parse(lex($test)).unwrap();
+      let mut interpreter = Interpreter::new();
+      assert_eq!(interpreter.run(&tree).map_err(|error| error.kind), $expected);
+    }
+  )
+}
+
 // Test interpreter fragments (no main function)
 test_fragment!(interpreter_numeric, r#"123"#, Ok(Value::Number(123)));
-test_fragment!(interpreter_string, r#""hello world""#, Ok(Value::String("helloworld".to_string())));
+test_fragment!(interpreter_string, r#""hello world""#, Ok(Value::String("hello world".to_string())));
 test_fragment!(interpreter_bool_true, r#"true"#, Ok(Value::Bool(true)));
 test_fragment!(interpreter_bool_false, r#"false"#, Ok(Value::Bool(false)));
 test_fragment!(interpreter_identifier, r#"x"#, Err(AsaErrorKind::UndefinedFunction));
@@ -57,7 +67,9 @@ test_fragment!(interpreter_function_call_more_args, r#"foo(a,b,c)"#, Err(AsaErro
 test_fragment!(interpreter_variable_define, r#"let x = 123;"#, Ok(Value::Number(123)));
 test_fragment!(interpreter_variable_init, r#"let x = 1;"#, Ok(Value::Number(1)));
 test_fragment!(interpreter_variable_bool, r#"let bool = true;"#, Ok(Value::Bool(true)));
-test_fragment!(interpreter_variable_string, r#"let string = "Hello World";"#, Ok(Value::String("HelloWorld".to_string())));
+test_fragment!(interpreter_variable_string, r#"let string = "Hello World";"#, Ok(Value::String("Hello World".to_string())));
+test_fragment!(interpreter_string_unicode, r#""héllo, wörld! 2 ✓""#, Ok(Value::String("héllo, wörld! 2 ✓".to_string())));
+test_fragment!(interpreter_variable_unicode, r#"let größe = 5;"#, Ok(Value::Number(5)));
 test_fragment!(interpreter_variable_init_no_space, r#"let x=1;"#, Ok(Value::Number(1)));
 test_fragment!(interpreter_math, r#"1 + 1"#, Ok(Value::Number(2)));
 test_fragment!(interpreter_math_no_space, r#"1-1"#, Ok(Value::Number(0)));
@@ -125,3 +137,366 @@ test_program!(final_test_9, r#"fn main() { if (1>2) {return 3;} else if (1 < 2)
 //test multiple else ifs
 test_program!(final_test_10, r#"fn main() { if (1>2) {return 3;} else if (1 == 2){return 2;} else if (1 < 2) {return 6;} else{return 5;}; }"#, Ok(Value::Number(6)));
 
+
+//test comments
+test_program!(comment_test_1, r#"// entry point
+fn main() { // fix 2 bugs!
+  let x = 1; /* the /* nested */ value */
+  return x + /* inline */ 2;
+}
+/* trailing */"#, Ok(Value::Number(3)));
+test_program!(comment_test_2, r#"fn main() { return "// not a comment"; }"#, Ok(Value::String("// not a comment".to_string())));
+
+//test optional else and standalone if statements
+test_program!(if_test_1, r#"fn main() { if (1 < 2) { return 1; } return 2; }"#, Ok(Value::Number(1)));
+test_program!(if_test_2, r#"fn main() { if (1 > 2) { return 1; } return 2; }"#, Ok(Value::Number(2)));
+test_program!(if_test_3, r#"fn main() { if (1 > 2) {} else {} return 3; }"#, Ok(Value::Number(3)));
+test_program!(if_test_4, r#"fn main() { let x = 4; if (x > 2) { let y = x; } return x; }"#, Ok(Value::Number(4)));
+test_program!(if_test_5, r#"fn main() { if (1 < 2) { let y = 1; } return y; }"#, Err(AsaErrorKind::UndefinedFunction));
+
+//test nested if/else-if chains followed by more statements
+test_program!(if_test_6, r#"fn main() { return classify(5); }
+fn classify(x) {
+  if (x < 0) {
+    return 0;
+  } else if (x < 10) {
+    if (x < 5) {
+      return 1;
+    } else if (x <= 5) {
+      return 2;
+    }
+    return 3;
+  } else {
+    return 4;
+  }
+  return 5;
+}"#, Ok(Value::Number(2)));
+
+//test nested if/else-if chains as the value of a function body
+test_program!(if_test_7, r#"fn main() { return classify(7); }
+fn classify(x) {
+  if (x < 0) {
+    0
+  } else if (x < 10) {
+    if (x < 5) { 1 } else if (x < 9) { 2 } else { 3 }
+  } else {
+    4
+  }
+}"#, Ok(Value::Number(2)));
+
+//test that only a tail expression gives a block its value
+test_script!(tail_test_1, r#"let y = { 1; }; y"#, Ok(Value::Nil));
+test_script!(tail_test_2, r#"let c = true; let z = if (c) { let z = 3; }; z"#, Ok(Value::Nil));
+test_script!(tail_test_3, r#"[{ if (true) { 1 } else { 2 } }, { if (true) { 1 } else { 2 }; }]"#, Ok(list(vec![Value::Number(1), Value::Nil])));
+test_script!(tail_test_4, r#"fn f() { let x = 5; } fn main() { return f(); }"#, Ok(Value::Nil));
+test_script!(tail_test_5, r#"fn main() { main2(); } fn main2() { 1 }"#, Ok(Value::Nil));
+
+//test if and blocks as expressions
+test_fragment!(interpreter_negation, r#"-5"#, Ok(Value::Number(-5)));
+test_fragment!(interpreter_negation_bool, r#"-true"#, Err(AsaErrorKind::TypeMismatch));
+test_program!(expression_test_1, r#"fn main() { let x = -5; let sign = if (x < 0) { -1 } else { 1 }; return sign; }"#, Ok(Value::Number(-1)));
+test_program!(expression_test_2, r#"fn main() { let y = { let a = 2; a + 3 }; return y; }"#, Ok(Value::Number(5)));
+test_program!(expression_test_3, r#"fn main() { double(4) } fn double(x) { x + x }"#, Ok(Value::Number(8)));
+test_program!(expression_test_4, r#"fn main() { let a = { let b = 1; b }; return b; }"#, Err(AsaErrorKind::UndefinedFunction));
+test_program!(expression_test_5, r#"fn main() { grade(72) }
+fn grade(score) {
+  let bonus = if (score >= 70) { 5 } else { 0 };
+  let score = score + bonus;
+  if (score < 50) { "fail" } else if (score < 80) { "pass" } else { "merit" }
+}"#, Ok(Value::String("pass".to_string())));
+test_program!(expression_test_6, r#"fn main() { let x = if (1 < 2) { return 7; } else { 0 }; return x; }"#, Ok(Value::Number(7)));
+
+//test ternary conditional operator
+test_fragment!(ternary_test_1, r#"1 < 2 ? 10 : 20"#, Ok(Value::Number(10)));
+test_fragment!(ternary_test_2, r#"true ? 1 : undefined()"#, Ok(Value::Number(1)));
+test_fragment!(ternary_test_3, r#"1 ? 2 : 3"#, Err(AsaErrorKind::TypeMismatch));
+test_fragment!(ternary_test_4, r#"1 + 1 > 1 && false ? 1 : 2 + 3"#, Ok(Value::Number(5)));
+test_program!(ternary_test_5, r#"fn main() { return sign(2) + sign(-7); }
+fn sign(x) { x < 0 ? -1 : x < 1 ? 0 : 1 }"#, Ok(Value::Number(0)));
+test_program!(ternary_test_6, r#"fn main() { let x = (1 > 2 ? 3 : 4) - 1; return x; }"#, Ok(Value::Number(3)));
+
+//test running programs as scripts, with or without main
+test_script!(script_test_1, r#"let x = 2; let y = x + 3; y"#, Ok(Value::Number(5)));
+test_script!(script_test_2, r#"double(21); fn double(x) { x + x }"#, Ok(Value::Number(42)));
+test_script!(script_test_3, r#"fn main() { return 1; } 2;"#, Ok(Value::Number(1)));
+test_script!(script_test_4, r#"fn helper() { 3 }"#, Ok(Value::Nil));
+test_script!(script_test_5, r#"let x = missing(); fn main() { return 1; }"#, Err(AsaErrorKind::UndefinedFunction));
+
+//test global variables and constants
+test_script!(global_test_1, r#"const LIMIT = 10; let base = 3; fn main() { return scale(2); } fn scale(x) { x + base + LIMIT }"#, Ok(Value::Number(15)));
+test_script!(global_test_2, r#"let count = 1; fn bump() { let count = count + 1; count } let bumped = bump(); count + bumped"#, Ok(Value::Number(3)));
+test_script!(global_test_3, r#"const LIMIT = 10; let LIMIT = 11; LIMIT"#, Err(AsaErrorKind::ConstantRedefined("LIMIT".to_string())));
+test_script!(global_test_4, r#"let x = 1; const x = 2;"#, Err(AsaErrorKind::ConstantRedefined("x".to_string())));
+test_script!(global_test_5, r#"fn main() { if (1 < 2) { return later; } } let later = 4;"#, Ok(Value::Number(4)));
+
+//test functions as values
+test_script!(function_value_test_1, r#"fn add(a, b) { a + b } let f = add; f(1, 2)"#, Ok(Value::Number(3)));
+test_script!(function_value_test_2, r#"fn apply(f, x) { f(x) } fn inc(x) { x + 1 } apply(inc, 41)"#, Ok(Value::Number(42)));
+test_script!(function_value_test_3, r#"fn twice(f, x) { f(f(x)) } fn pick(wide) { wide ? twice : once } fn once(f, x) { f(x) } fn dbl(x) { x + x }
+let g = pick(true); g(dbl, 3)"#, Ok(Value::Number(12)));
+test_script!(function_value_test_4, r#"let f = 5; f(1)"#, Err(AsaErrorKind::TypeMismatch));
+test_script!(function_value_test_5, r#"fn add(a, b) { a + b } fn main() { let op = add; return op(2, 2); }"#, Ok(Value::Number(4)));
+
+//test closures and lambdas
+test_script!(closure_test_1, r#"let offset = 10; let add = |x| x + offset; add(5)"#, Ok(Value::Number(15)));
+test_script!(closure_test_2, r#"fn adder(n) { fn(x) { return x + n; } } let add5 = adder(5); let add7 = adder(7); add5(1) + add7(1)"#, Ok(Value::Number(14)));
+test_script!(closure_test_3, r#"fn apply(f, x) { f(x) } fn main() { let k = 3; return apply(|x| x - k, 10); }"#, Ok(Value::Number(7)));
+test_script!(closure_test_4, r#"let f = || 1; let g = |a, b| a + b; f() + g(2, 3)"#, Ok(Value::Number(6)));
+test_script!(closure_test_5, r#"let fact = |n| n < 2 ? 1 : n + fact(n - 1); fact(4)"#, Ok(Value::Number(10)));
+test_script!(closure_test_6, r#"let x = 1; let f = fn() { let x = 5; x }; let y = f(); x + y"#, Ok(Value::Number(6)));
+test_script!(closure_test_7, r#"fn counter() { let n = 1; let get = || n; let n = 2; get } let get = counter(); get()"#, Ok(Value::Number(2)));
+test_script!(closure_test_8, r#"fn make(n) { let g = || n; let h = || g(); [h] } fn outer() { let f = make(3)[0]; f() } let f = make(4)[0]; outer() + f()"#, Ok(Value::Number(7)));
+
+// The scope of a call that stored a closure in a local is freed once the
+// call is over, so the only copies of `xs` left are the global and the result.
+#[test]
+fn closure_release_test() {
+  let tree = parse(lex(r#"let xs = [1, 2]; fn f(ys) { let g = || ys; g() } fn h(ys) { { let g = || ys; g } } fn call(ys) { let k = h(ys); k() } f(xs); call(xs); xs"#)).unwrap();
+  let mut interpreter = Interpreter::new();
+  match interpreter.run(&tree) {
+    Ok(Value::List(xs)) => assert_eq!(std::rc::Rc::strong_count(&xs), 2),
+    result => panic!("unexpected result {:?}", result),
+  }
+}
+
+//test default parameters and named arguments
+test_script!(argument_test_1, r#"fn connect(host, port = 8080) { port } connect("db")"#, Ok(Value::Number(8080)));
+test_script!(argument_test_2, r#"fn connect(host, port = 8080) { port } connect("db", port: 5432)"#, Ok(Value::Number(5432)));
+test_script!(argument_test_3, r#"fn connect(host, port = 8080) { host } connect(port: 1, host: "db")"#, Ok(Value::String("db".to_string())));
+test_script!(argument_test_4, r#"fn range(start, end = start + 10) { end - start } range(5)"#, Ok(Value::Number(10)));
+test_script!(argument_test_5, r#"fn connect(host, port = 8080) { port } connect(port: 1)"#, Err(AsaErrorKind::MissingArgument("host".to_string())));
+test_script!(argument_test_6, r#"fn connect(host, port = 8080) { port } connect("db", timeout: 1)"#, Err(AsaErrorKind::UnknownArgument("timeout".to_string())));
+test_script!(argument_test_7, r#"fn connect(host, port = 8080) { port } connect("db", 1, 2)"#, Err(AsaErrorKind::Generic("`connect` takes at most 2 arguments but 3 were given".to_string())));
+test_script!(argument_test_8, r#"fn connect(host, port = 8080) { port } connect("db", host: "x")"#, Err(AsaErrorKind::Generic("Argument `host` was given more than once".to_string())));
+test_script!(argument_test_9, r#"let scale = |x, by = 2| x - by; scale(5) + scale(5, by: 1)"#, Ok(Value::Number(7)));
+
+//test rest parameters and spread arguments
+test_script!(variadic_test_1, r#"fn collect(first, ...rest) { rest } collect(1, 2, 3)"#, Ok(Value::List(std::rc::Rc::new(vec![Value::Number(2), Value::Number(3)]))));
+test_script!(variadic_test_2, r#"fn collect(first, ...rest) { rest } collect(1)"#, Ok(Value::List(std::rc::Rc::new(vec![]))));
+test_script!(variadic_test_3, r#"fn sub(a, b) { a - b } fn apply(f, ...args) { f(...args) } apply(sub, 5, 3)"#, Ok(Value::Number(2)));
+test_script!(variadic_test_4, r#"fn log(level = 1, ...parts) { level } fn forward(...args) { log(...args) } forward() + forward(4, 5, 6)"#, Ok(Value::Number(5)));
+test_script!(variadic_test_5, r#"fn f(a, b) { a } f(...1)"#, Err(AsaErrorKind::TypeMismatch));
+test_script!(variadic_test_6, r#"fn f(a, b) { b } fn g(...xs) { f(0, ...xs) } g(7)"#, Ok(Value::Number(7)));
+
+//test lists
+fn list(values: Vec<Value>) -> Value {
+  Value::List(std::rc::Rc::new(values))
+}
+
+test_fragment!(list_test_1, r#"[1, "two", [true]]"#, Ok(list(vec![Value::Number(1), Value::String("two".to_string()), list(vec![Value::Bool(true)])])));
+test_fragment!(list_test_2, r#"[]"#, Ok(list(vec![])));
+test_script!(list_test_3, r#"let xs = [10, 20, 30]; xs[0] + xs[-1]"#, Ok(Value::Number(40)));
+test_script!(list_test_4, r#"let xs = [10, 20, 30]; xs[3]"#, Err(AsaErrorKind::IndexOutOfBounds(3)));
+test_script!(list_test_5, r#"let xs = [10, 20, 30]; xs[-4]"#, Err(AsaErrorKind::IndexOutOfBounds(-4)));
+test_script!(list_test_6, r#"let grid = [[1, 2], [3, 4]]; grid[1][0] = 5; grid"#, Ok(list(vec![list(vec![Value::Number(1), Value::Number(2)]), list(vec![Value::Number(5), Value::Number(4)])])));
+test_script!(list_test_7, r#"let a = [1, 2]; let b = a; b[0] = 9; a[0] + b[0]"#, Ok(Value::Number(10)));
+test_script!(list_test_8, r#"[1, [2, 3]] == [1, [2, 3]] && [1] != [2] && [1] != 1"#, Ok(Value::Bool(true)));
+test_script!(list_test_9, r#"fn main() { let xs = [1, 2, 3]; if (true) { xs[1] = 7; } return xs[1]; }"#, Ok(Value::Number(7)));
+test_script!(list_test_10, r#"let xs = [1]; fn change() { xs[0] = 2; } change()"#, Err(AsaErrorKind::ImmutableVariable("xs".to_string())));
+test_script!(list_test_11, r#"const XS = [1]; XS[0] = 2;"#, Err(AsaErrorKind::ImmutableVariable("XS".to_string())));
+test_script!(list_test_12, r#"let xs = [1]; let f = || { xs[0] = 2; }; f()"#, Err(AsaErrorKind::ImmutableVariable("xs".to_string())));
+test_script!(list_test_13, r#""héllo"[1]"#, Ok(Value::String("é".to_string())));
+//test that assignments a function can't make are rejected before anything runs
+test_script!(list_test_14, r#"let xs = [1]; fn change() { xs[0] = 2; } xs"#, Err(AsaErrorKind::ImmutableVariable("xs".to_string())));
+test_script!(list_test_15, r#"fn f() { let ys = [1]; let g = fn() { ys[0] = 2; }; ys } 1"#, Err(AsaErrorKind::ImmutableVariable("ys".to_string())));
+test_script!(list_test_16, r#"fn f() { { let a = [1]; } a[0] = 2; } 1"#, Err(AsaErrorKind::VariableNotDefined("a".to_string())));
+test_script!(list_test_17, r#"fn f(xs, v) { let g = |ys| { ys[0] = 3; ys }; xs[0] = match v { n => { let m = [n]; m[0] = n + 1; m[0] } }; [xs, g(xs)] } f([1], 5)"#, Ok(list(vec![list(vec![Value::Number(6)]), list(vec![Value::Number(3)])])));
+
+//test higher-order built-in functions
+test_script!(builtin_test_1, r#"let xs = [1, 2, 3, 4]; map(xs, |x| x + x)"#, Ok(list(vec![Value::Number(2), Value::Number(4), Value::Number(6), Value::Number(8)])));
+test_script!(builtin_test_2, r#"fn small(x) { x < 3 } filter([1, 2, 3, 4], small)"#, Ok(list(vec![Value::Number(1), Value::Number(2)])));
+test_script!(builtin_test_3, r#"reduce([1, 2, 3, 4], |acc, x| acc + x, 0)"#, Ok(Value::Number(10)));
+test_script!(builtin_test_4, r#"map([[1], [], [1, 2]], len)"#, Ok(list(vec![Value::Number(1), Value::Number(0), Value::Number(2)])));
+test_script!(builtin_test_5, r#"len(5)"#, Err(AsaErrorKind::TypeMismatch));
+//test that any function value can be passed to the higher-order builtins
+test_script!(builtin_test_6, r#"fn add(a, b) { a + b } fn dbl(x) { add(x, x) } reduce(map([1, 2, 3], dbl), add, 0)"#, Ok(Value::Number(12)));
+test_script!(builtin_test_7, r#"fn above(n) { |x| x > n } let big = above(4); filter([1, 5, 9], big)"#, Ok(list(vec![Value::Number(5), Value::Number(9)])));
+test_script!(builtin_test_8, r#"fn main() { let f = fn(x) { return [x]; }; return map([1, 2], f); }"#, Ok(list(vec![list(vec![Value::Number(1)]), list(vec![Value::Number(2)])])));
+test_script!(builtin_test_9, r#"map([1], 5)"#, Err(AsaErrorKind::TypeMismatch));
+
+//test maps
+fn string(value: &str) -> Value {
+  Value::String(value.to_string())
+}
+
+test_script!(map_test_1, r#"let m = {"name": "x", "count": 3}; m["count"]"#, Ok(Value::Number(3)));
+test_script!(map_test_2, r#"let m = {"b": 1, "a": 2, 3: 3}; keys(m)"#, Ok(list(vec![string("b"), string("a"), Value::Number(3)])));
+test_script!(map_test_3, r#"let m = {"a": 1}; m["b"] = 2; m["a"] = 3; values(m)"#, Ok(list(vec![Value::Number(3), Value::Number(2)])));
+test_script!(map_test_4, r#"let m = {"a": 1}; m["b"]"#, Err(AsaErrorKind::KeyNotFound("\"b\"".to_string())));
+test_script!(map_test_5, r#"let m = {"a": 1, "b": 2}; let n = remove(m, "a"); [has(m, "a"), has(n, "a"), len(n)]"#, Ok(list(vec![Value::Bool(true), Value::Bool(false), Value::Number(1)])));
+test_script!(map_test_6, r#"{"a": 1, "b": 2} == {"b": 2, "a": 1,}"#, Ok(Value::Bool(true)));
+test_script!(map_test_7, r#"let m = {"xs": [1, 2]}; m["xs"][0] = 5; m["xs"]"#, Ok(list(vec![Value::Number(5), Value::Number(2)])));
+test_script!(map_test_8, r#"let m = {}; m[[1]] = 2;"#, Err(AsaErrorKind::TypeMismatch));
+test_script!(map_test_9, r#"let x = { let a = 1; a + 1 }; let m = { a: x }; m[1]"#, Err(AsaErrorKind::UndefinedFunction));
+test_script!(map_test_10, r#"let a = 1; let m = { a: { a + 1 } }; m[1]"#, Ok(Value::Number(2)));
+test_script!(map_test_11, r#"remove({}, 1)"#, Err(AsaErrorKind::KeyNotFound("1".to_string())));
+
+//test structs
+test_script!(struct_test_1, r#"struct Point { x, y } let p = Point { x: 1, y: 2 }; p.x + p.y"#, Ok(Value::Number(3)));
+test_script!(struct_test_2, r#"let p = Point { y: 2, x: 1 }; p.x = 5; p.x - p.y struct Point { x, y, }"#, Ok(Value::Number(3)));
+test_script!(struct_test_3, r#"struct Point { x, y } let p = Point { x: 1, y: 2 }; p.z"#, Err(AsaErrorKind::UnknownField("Point".to_string(), "z".to_string())));
+test_script!(struct_test_4, r#"struct Point { x, y } Point { x: 1, y: 2, z: 3 }"#, Err(AsaErrorKind::UnknownField("Point".to_string(), "z".to_string())));
+test_script!(struct_test_5, r#"struct Point { x, y } Point { x: 1 }"#, Err(AsaErrorKind::MissingField("Point".to_string(), "y".to_string())));
+test_script!(struct_test_6, r#"Point { x: 1 }"#, Err(AsaErrorKind::UndefinedType("Point".to_string())));
+test_script!(struct_test_7, r#"struct Point { x, y } let p = Point { x: 1, y: 2 }; let q = p; q.x = 3; [p.x, q.x, p == Point { y: 2, x: 1 }]"#, Ok(list(vec![Value::Number(1), Value::Number(3), Value::Bool(true)])));
+test_script!(struct_test_8, r#"struct Line { from, to } struct Point { x, y } let l = Line { from: Point { x: 0, y: 0 }, to: Point { x: 3, y: 4 } }; l.to.y = 5; l.to.y"#, Ok(Value::Number(5)));
+test_script!(struct_test_9, r#"struct Point { x, y } let p = Point { x: 1, y: 2 }; p.z = 1;"#, Err(AsaErrorKind::UnknownField("Point".to_string(), "z".to_string())));
+test_script!(struct_test_10, r#"let xs = [1]; xs.x"#, Err(AsaErrorKind::TypeMismatch));
+test_script!(struct_test_11, r#"struct P { x } struct P { y } P { y: 1 }"#, Err(AsaErrorKind::Generic("Type `P` is defined more than once".to_string())));
+test_script!(struct_test_12, r#"enum P { A } struct P { y }"#, Err(AsaErrorKind::Generic("Type `P` is defined more than once".to_string())));
+
+//test enums and match
+test_script!(match_test_1, r#"
+enum Shape { Circle(r), Rect(w, h), Empty }
+fn area(shape) {
+  match shape {
+    Circle(r) => r + r + r,
+    Rect(w, h) => w + h,
+    Empty => 0,
+  }
+}
+[area(Circle(2)), area(Rect(2, 5)), area(Empty)]"#, Ok(list(vec![Value::Number(6), Value::Number(7), Value::Number(0)])));
+test_script!(match_test_2, r#"enum Shape { Circle(r), Rect(w, h) } match Rect(1, 2) { Circle(r) => r }"#, Err(AsaErrorKind::NonExhaustiveMatch("Rect(_, _)".to_string())));
+test_script!(match_test_3, r#"fn name(n) { match n { 0 => "zero", 1 => "one", -1 => "minus one", _ => "many" } } [name(1), name(-1), name(7)]"#, Ok(list(vec![Value::String("one".to_string()), Value::String("minus one".to_string()), Value::String("many".to_string())])));
+test_script!(match_test_4, r#"match 5 { 0 => "zero", 1 => "one" }"#, Err(AsaErrorKind::NonExhaustiveMatch("_".to_string())));
+test_script!(match_test_5, r#"match 1 < 2 { true => "yes", false => "no" }"#, Ok(Value::String("yes".to_string())));
+test_script!(match_test_6, r#"enum Option { Some(value), None } match Some(None) { Some(Some(x)) => x, None => 0 }"#, Err(AsaErrorKind::NonExhaustiveMatch("Some(None)".to_string())));
+test_script!(match_test_7, r#"enum Option { Some(value), None } let x = 3; let y = match Some(4) { Some(x) => x + 1, None => x }; [x, y]"#, Ok(list(vec![Value::Number(3), Value::Number(5)])));
+test_script!(match_test_8, r#"enum Result { Ok(value), Err(message) }
+fn divide(a, b) { if (b == 0) { Err("division by zero") } else { Ok(a - b) } }
+match divide(1, 0) { Ok(value) => value, Err(message) => message }"#, Ok(Value::String("division by zero".to_string())));
+test_script!(match_test_9, r#"enum Shape { Circle(r) } match Circle(1) { Square(r) => r, _ => 0 }"#, Err(AsaErrorKind::UndefinedVariant("Square".to_string())));
+test_script!(match_test_10, r#"enum Shape { Circle(r) } Circle(1, 2)"#, Err(AsaErrorKind::Generic("`Circle` takes 1 values but 2 were given".to_string())));
+test_script!(match_test_11, r#"enum Color { Red, Green } [Red == Red, Red == Green, map([1, 2], Some)] enum Option { Some(value), None }"#, Ok(list(vec![Value::Bool(true), Value::Bool(false), list(vec![
+  Value::Variant(std::rc::Rc::new(Variant{enum_name: "Option".to_string(), name: "Some".to_string(), values: vec![Value::Number(1)]})),
+  Value::Variant(std::rc::Rc::new(Variant{enum_name: "Option".to_string(), name: "Some".to_string(), values: vec![Value::Number(2)]})),
+])])));
+test_script!(match_test_12, r#"let x = 1; match x { 1 => { let y = 2; x + y } _ => 0 }"#, Ok(Value::Number(3)));
+test_script!(match_test_13, r#"let x = 5; match x { }"#, Err(AsaErrorKind::NonExhaustiveMatch("_".to_string())));
+test_script!(match_test_14, r#"struct P { x } fn get(p) { p.x } [match (P { x: 1 }) { p => p.x }, match get(P { x: 2 }) { n => n }, match [P { x: 3 }][0].x { n => n }]"#, Ok(list(vec![Value::Number(1), Value::Number(2), Value::Number(3)])));
+test_script!(match_test_15, r#"enum Shape { Circle(r), Rect(w, h) } match 5 { Circle(r) => r, Rect(w, h) => w }"#, Err(AsaErrorKind::TypeMismatch));
+test_script!(match_test_16, r#"match 5 { true => 1, false => 2 }"#, Err(AsaErrorKind::TypeMismatch));
+test_script!(match_test_17, r#"enum A { X(v) } enum B { Y } match X(5) { X(Y) => 1 }"#, Err(AsaErrorKind::TypeMismatch));
+test_script!(match_test_18, r#"enum Shape { Circle(r) } fn never(s) { match s { Circle(1) => 1 } } 1"#, Err(AsaErrorKind::NonExhaustiveMatch("Circle(_)".to_string())));
+test_script!(match_test_19, r#"enum Color { Red } match Red { Red => 1, true => 2, _ => 3 }"#, Err(AsaErrorKind::Generic("Patterns in the same place of a `match` can't be for both a `Color` and a bool".to_string())));
+test_script!(match_test_20, r#"enum A { X(v) } enum B { Y } match X(Y) { X(Y) => 1, X(5) => 2, _ => 3 }"#, Err(AsaErrorKind::Generic("Patterns in the same place of a `match` can't be for both a `B` and a number".to_string())));
+
+//test destructuring
+test_script!(destructure_test_1, r#"let pair = [1, 2]; let [a, b] = pair; b - a"#, Ok(Value::Number(1)));
+test_script!(destructure_test_2, r#"struct Point { x, y } let p = Point { x: 3, y: 4 }; let Point { x, y: height } = p; x + height"#, Ok(Value::Number(7)));
+test_script!(destructure_test_3, r#"fn f([a, b]) { a + b } f([5, 6])"#, Ok(Value::Number(11)));
+test_script!(destructure_test_4, r#"let [first, ...rest] = [1, 2, 3]; rest"#, Ok(list(vec![Value::Number(2), Value::Number(3)])));
+test_script!(destructure_test_5, r#"let [a, b] = [1, 2, 3];"#, Err(AsaErrorKind::PatternMismatch("`[a, b]` expected a list of 2 elements, found 3".to_string())));
+test_script!(destructure_test_6, r#"let [a, _, ...rest] = [1];"#, Err(AsaErrorKind::PatternMismatch("`[a, _, ...rest]` expected a list of at least 2 elements, found 1".to_string())));
+test_script!(destructure_test_7, r#"fn f([a, b]) { a } f(5)"#, Err(AsaErrorKind::PatternMismatch("`[a, b]` expected a list, found a number".to_string())));
+test_script!(destructure_test_8, r#"struct Point { x, y } struct Size { w, h } let Point { x } = Size { w: 1, h: 2 };"#, Err(AsaErrorKind::PatternMismatch("`Point { x }` expected a `Point`, found a `Size`".to_string())));
+test_script!(destructure_test_9, r#"struct Point { x, y } let Point { z } = Point { x: 1, y: 2 };"#, Err(AsaErrorKind::UnknownField("Point".to_string(), "z".to_string())));
+test_script!(destructure_test_10, r#"let points = [[1, 2], [3, 4]]; map(points, |[x, y]| y - x)"#, Ok(list(vec![Value::Number(1), Value::Number(1)])));
+test_script!(destructure_test_11, r#"fn f([a, b] = [1, 2], c = a) { [a, b, c] } f()"#, Ok(list(vec![Value::Number(1), Value::Number(2), Value::Number(1)])));
+test_script!(destructure_test_12, r#"fn f([a, b]) { a } f()"#, Err(AsaErrorKind::MissingArgument("[a, b]".to_string())));
+test_script!(destructure_test_13, r#"const a = 1; let [a, b] = [2, 3];"#, Err(AsaErrorKind::ConstantRedefined("a".to_string())));
+
+//test methods
+test_script!(method_test_1, r#"
+struct Point { x, y }
+impl Point {
+  fn sum(self) { self.x + self.y }
+  fn moved(self, dx, dy = 0) { Point { x: self.x + dx, y: self.y + dy } }
+}
+let p = Point { x: 1, y: 2 };
+[p.sum(), p.moved(3).sum(), p.moved(1, dy: 1).y]"#, Ok(list(vec![Value::Number(3), Value::Number(6), Value::Number(3)])));
+test_script!(method_test_2, r#"impl Shape { fn size(self) { match self { Square(s) => s, Dot => 0 } } } enum Shape { Square(s), Dot } [Square(4).size(), Dot.size()]"#, Ok(list(vec![Value::Number(4), Value::Number(0)])));
+test_script!(method_test_3, r#"[1, 2, 3].map(|x| x + 1).filter(|x| x > 2).len()"#, Ok(Value::Number(2)));
+test_script!(method_test_4, r#"let m = {"a": 1, "b": 2}; [m.keys(), m.has("b"), m.remove("b").len(), "abc".len()]"#, Ok(list(vec![list(vec![Value::String("a".to_string()), Value::String("b".to_string())]), Value::Bool(true), Value::Number(1), Value::Number(3)])));
+test_script!(method_test_5, r#"struct Point { x, y } let p = Point { x: 1, y: 2 }; p.dist()"#, Err(AsaErrorKind::UndefinedMethod("Point".to_string(), "dist".to_string())));
+test_script!(method_test_6, r#""abc".keys()"#, Err(AsaErrorKind::UndefinedMethod("string".to_string(), "keys".to_string())));
+test_script!(method_test_7, r#"struct Point { x, y } impl Point { fn origin() { 0 } }"#, Err(AsaErrorKind::Generic("Method `origin` of `Point` must take `self` as its first parameter".to_string())));
+test_script!(method_test_8, r#"impl Point { fn x(self) { 0 } }"#, Err(AsaErrorKind::UndefinedType("Point".to_string())));
+test_script!(method_test_9, r#"struct A { v } struct B { v } impl A { fn get(self) { 1 } } impl B { fn get(self) { 2 } } fn get(x) { 3 } [A { v: 0 }.get(), B { v: 0 }.get(), get(0)]"#, Ok(list(vec![Value::Number(1), Value::Number(2), Value::Number(3)])));
+test_script!(method_test_10, r#"struct Counter { n } impl Counter { fn bump(self) { self.n = self.n + 1; self } } let c = Counter { n: 0 }; let d = c.bump(); [c.n, d.n]"#, Ok(list(vec![Value::Number(0), Value::Number(1)])));
+
+//test nil
+test_fragment!(nil_test_1, r#"nil"#, Ok(Value::Nil));
+test_script!(nil_test_2, r#"[nil == nil, nil == false, nil != 0]"#, Ok(list(vec![Value::Bool(true), Value::Bool(false), Value::Bool(true)])));
+test_script!(nil_test_3, r#"let x = if (1 > 2) { 1 }; x"#, Ok(Value::Nil));
+test_script!(nil_test_4, r#"fn find(xs, x) { let found = filter(xs, |y| y == x); if (len(found) > 0) { return found[0]; } nil } [find([1, 2], 2) ?? 0, find([1, 2], 3) ?? 0]"#, Ok(list(vec![Value::Number(2), Value::Number(0)])));
+test_script!(nil_test_5, r#"struct Node { value, next } let list = Node { value: 1, next: Node { value: 2, next: nil } }; [list.next?.value, list.next.next?.value, list.next.next?.next?.value ?? -1]"#, Ok(list(vec![Value::Number(2), Value::Nil, Value::Number(-1)])));
+test_script!(nil_test_6, r#"let x = nil; x?.len(undefined)"#, Ok(Value::Nil));
+test_script!(nil_test_7, r#"let x = nil; x.len()"#, Err(AsaErrorKind::UndefinedMethod("nil".to_string(), "len".to_string())));
+test_script!(nil_test_8, r#"false ?? true ? 1 : 2"#, Ok(Value::Number(2)));
+test_script!(nil_test_9, r#"match nil { nil => "none", _ => "some" }"#, Ok(Value::String("none".to_string())));
+
+#[test]
+fn nil_printing() {
+  let tree = parse(lex(r#"struct P { x, s } enum E { A(v), B } [nil, "a", {"k": [true, B]}, P { x: A(1), s: "b" }, len]"#)).unwrap();
+  let value = Interpreter::new().run(&tree).unwrap();
+  assert_eq!(value.to_string(), r#"[nil, "a", {"k": [true, B]}, P { x: A(1), s: "b" }, <fn len>]"#);
+  assert_eq!(Value::String("a".to_string()).to_string(), "a");
+}
+
+//test try, catch and throw
+test_script!(try_test_1, r#"try { [1, 2][5] } catch (e) { [e.kind, e.message] }"#, Ok(list(vec![Value::String("IndexOutOfBounds".to_string()), Value::String("index 5 is out of bounds".to_string())])));
+test_script!(try_test_2, r#"try { 1 } catch (e) { 2 }"#, Ok(Value::Number(1)));
+test_script!(try_test_3, r#"fn check(x) { if (x < 0) { throw "negative"; } x } try { check(-1) } catch (e) { [e.kind, e.value] }"#, Ok(list(vec![Value::String("Thrown".to_string()), Value::String("negative".to_string())])));
+test_script!(try_test_4, r#"fn check(x) { if (x < 0) { throw x; } x } check(-1)"#, Err(AsaErrorKind::Thrown(Value::Number(-1))));
+test_script!(try_test_5, r#"let m = {"a": 1}; let v = try { m["b"] } catch (Error { kind, message }) { message }; v"#, Ok(Value::String("key \"b\" not found".to_string())));
+test_script!(try_test_6, r#"try { throw 1; } catch (e) { throw e.value + 1; }"#, Err(AsaErrorKind::Thrown(Value::Number(2))));
+test_script!(try_test_7, r#"fn f() { try { return 1; } catch (e) { return 2; } return 3; } f()"#, Ok(Value::Number(1)));
+test_script!(try_test_8, r#"let x = 1; try { let x = 2; undefined } catch (_) { x }"#, Ok(Value::Number(1)));
+test_script!(try_test_9, r#"try { len(5) } catch (e) { e.kind }"#, Ok(Value::String("TypeMismatch".to_string())));
+test_script!(try_test_10, r#"try { try { throw "inner"; } catch (e) { throw e.message; } } catch (e) { e.value }"#, Ok(Value::String("inner".to_string())));
+test_script!(try_test_11, r#"[try { 2147483647 + 1 } catch (e) { e.kind == "NumberOverflow" }, try { -2147483647 - 2 } catch (e) { e.kind }, try { 1 - -2147483647 } catch (e) { e.kind }]"#, Ok(list(vec![Value::Bool(true), Value::String("NumberUnderflow".to_string()), Value::String("NumberOverflow".to_string())])));
+
+//test call stacks of errors
+fn frame(function: &str, line: u32, col: u32) -> CallFrame {
+  CallFrame { function: function.to_string(), line, col }
+}
+
+#[test]
+fn trace_test_1() {
+  let tree = parse(lex(r#"fn countdown(n) {
+  if (n == 0) { return [][0]; }
+  countdown(n - 1)
+}
+fn main() { countdown(2) }"#)).unwrap();
+  let error = Interpreter::new().run(&tree).unwrap_err();
+  assert_eq!(error.kind, AsaErrorKind::IndexOutOfBounds(0));
+  assert_eq!(error.trace, vec![frame("main", 0, 0), frame("countdown", 5, 13), frame("countdown", 3, 3), frame("countdown", 3, 3)]);
+  assert_eq!(error.to_string(), "index 0 is out of bounds
+Call stack (most recent call last):
+  `main`
+  `countdown` called at 5:13
+  `countdown` called at 3:3
+  `countdown` called at 3:3");
+}
+
+#[test]
+fn trace_test_2() {
+  let tree = parse(lex(r#"fn fail() { [][0] }
+let caught = try { fail() } catch (e) { 0 };
+struct P { x }
+impl P { fn get(self, key) { let m = {}; m[key] } }
+[1, 2].map(|x| P { x: x }.get("y"))"#)).unwrap();
+  let error = Interpreter::new().run(&tree).unwrap_err();
+  assert_eq!(error.kind, AsaErrorKind::KeyNotFound("\"y\"".to_string()));
+  assert_eq!(error.trace, vec![frame("list.map", 5, 7), frame("<lambda>", 5, 7), frame("P.get", 5, 26)]);
+}
+
+#[test]
+fn trace_test_3() {
+  let tree = parse(lex(r#"let x = [][0];"#)).unwrap();
+  let error = Interpreter::new().run(&tree).unwrap_err();
+  assert_eq!(error.trace, vec![]);
+  assert_eq!(error.to_string(), "index 0 is out of bounds");
+}
+
+#[test]
+fn trace_test_4() {
+  let tree = parse(lex(r#"fn check(x) { return [][x]; }
+fn main() { return map([1], check); }"#)).unwrap();
+  let error = Interpreter::new().run(&tree).unwrap_err();
+  assert_eq!(error.trace, vec![frame("main", 0, 0), frame("map", 2, 20), frame("check", 2, 20)]);
+  let tree = parse(lex(r#"fn check(x) { return [][x]; } fn main() { return filter([1], check); }"#)).unwrap();
+  let error = Interpreter::new().run(&tree).unwrap_err();
+  assert_eq!(error.trace, vec![frame("main", 0, 0), frame("filter", 1, 50), frame("check", 1, 50)]);
+}
@@ -28,12 +28,12 @@ test!(parser_string, r#""hello""#, string, String{value: "hello".to_string()});
 test!(parser_function_call, r#"foo()"#, function_call, FunctionCall{name: vec![102, 111, 111], children: vec![
   FunctionArguments{ children: vec![
   ]}
-]});
+], location: Location{line: 1, col: 1}});
 test!(parser_function_call_one_arg, r#"foo(a)"#, function_call, FunctionCall{name: vec![102, 111, 111], children: vec![
   FunctionArguments{ children: vec![
     Expression { children: vec![Identifier { value: vec![97] }]}
   ]}
-]});
+], location: Location{line: 1, col: 1}});
 test!(parser_variable_define_number, r#"let a = 123"#, variable_define, VariableDefine{children: vec![
   Identifier { value: vec![97] },
   Expression { children: vec![Number{value: 123 }]}
@@ -61,7 +61,7 @@ test!(parser_variable_function_call, r#"let a = foo()"#, variable_define, Variab
     FunctionCall{name: vec![102, 111, 111], children: vec![
       FunctionArguments{ children: vec![
       ]}
-    ]}
+    ], location: Location{line: 1, col: 9}}
   ]}
 ]});
 test!(parser_function_define, r#"fn a(){return 1;}"#, function_define, FunctionDefine{
@@ -79,8 +79,8 @@ test!(parser_function_define_multi_statements, r#"fn add(a,b){let x=a+b;return x
   name: vec![97, 100, 100],
   children: vec![
     FunctionArguments{ children: vec![
-      Expression { children: vec![Identifier { value: vec![97] }] },
-      Expression { children: vec![Identifier { value: vec![98] }] },
+      Identifier { value: vec![97] },
+      Identifier { value: vec![98] },
     ] },
     FunctionStatements{ children: vec![
       VariableDefine{children: vec![
@@ -277,6 +277,290 @@ test!(parser_added_test_6, r#"if(1 > 2){let x = 1;} else if (1<=2) {let x = 2;}
 );
 
 
+//testing if statement without else and with an empty block
+test!(parser_if_no_else, r#"if(1 > 2){}"#, if_statement,
+  IfStatement { case: vec![
+    ConditionalExpression { children: vec![
+      Number{value: 1}, GreaterThan{}, Number{value: 2}
+    ]}],
+    statements: vec![],
+    else_statements: vec![],
+  }
+);
+
+//testing else if without a final else
+test!(parser_if_else_if_no_else, r#"if(1 > 2){let x = 1;} else if (1<=2) {}"#, if_statement,
+  IfStatement { case: vec![
+    ConditionalExpression { children: vec![
+      Number{value: 1}, GreaterThan{}, Number{value: 2}
+    ]}],
+    statements: vec![
+      VariableDefine { children: vec![
+        Identifier { value: vec![120] },
+        Expression { children: vec![Number{value: 1}]}
+      ]}
+    ],
+    else_statements: vec![
+      ElseIfStatement{ case: vec![
+        ConditionalExpression { children: vec![
+          Number{value: 1}, LessThanOrEqualTo{}, Number{value: 2}
+        ]}],
+        statements: vec![],
+      }
+    ],
+  }
+);
+
+//testing if and block expressions
+test!(parser_if_expression, r#"let sign = if (x < 0) { -1 } else { 1 };"#, statement,
+  VariableDefine { children: vec![
+    Identifier { value: b"sign".to_vec() },
+    Expression { children: vec![
+      IfStatement { case: vec![
+        ConditionalExpression { children: vec![
+          Identifier { value: vec![120] }, LessThan{}, Number{value: 0}
+        ]}],
+        statements: vec![
+          Tail { children: vec![Expression { children: vec![Negation { children: vec![Number{value: 1}] }]}] }
+        ],
+        else_statements: vec![
+          ElseStatement { statements: vec![Tail { children: vec![Expression { children: vec![Number{value: 1}]}] }] }
+        ],
+      }
+    ]}
+  ]}
+);
+
+test!(parser_block_expression, r#"{ let a = 1; a }"#, expression,
+  Expression { children: vec![
+    Block { children: vec![
+      VariableDefine { children: vec![
+        Identifier { value: vec![97] },
+        Expression { children: vec![Number{value: 1}]}
+      ]},
+      Tail { children: vec![Expression { children: vec![Identifier { value: vec![97] }]}] }
+    ]}
+  ]}
+);
+
+test!(parser_block_like_tail, r#"{ if (a) {}; match a {} { 1 } }"#, block_expression,
+  Block { children: vec![
+    IfStatement { case: vec![Identifier { value: vec![97] }], statements: vec![], else_statements: vec![] },
+    Match { children: vec![Identifier { value: vec![97] }] },
+    Tail { children: vec![
+      Block { children: vec![Tail { children: vec![Expression { children: vec![Number{value: 1}]}] }] }
+    ]}
+  ]}
+);
+
+//testing precedence and the ternary operator
+test!(parser_ternary, r#"a || b ? 1 + 2 : c ? 3 : 4"#, ternary,
+  TernaryExpression { children: vec![
+    ConditionalExpression { children: vec![
+      Identifier { value: vec![97] }, Or{}, Identifier { value: vec![98] }
+    ]},
+    MathExpression { name: b"add".to_vec(), children: vec![Number{value: 1}, Number{value: 2}] },
+    TernaryExpression { children: vec![
+      Identifier { value: vec![99] }, Number{value: 3}, Number{value: 4}
+    ]},
+  ]}
+);
+
+test!(parser_precedence, r#"1 - 2 + 3 < 4 && true"#, conditional,
+  ConditionalExpression { children: vec![
+    ConditionalExpression { children: vec![
+      MathExpression { name: b"add".to_vec(), children: vec![
+        MathExpression { name: b"sub".to_vec(), children: vec![Number{value: 1}, Number{value: 2}] },
+        Number{value: 3}
+      ]},
+      LessThan{},
+      Number{value: 4}
+    ]},
+    And{},
+    Bool{value: true}
+  ]}
+);
+
+//testing lambdas
+test!(parser_lambda, r#"|x, y| x + y"#, lambda,
+  Lambda { children: vec![
+    FunctionArguments { children: vec![
+      Identifier { value: vec![120] },
+      Identifier { value: vec![121] },
+    ]},
+    FunctionStatements { children: vec![
+      Tail { children: vec![Expression { children: vec![
+        MathExpression { name: b"add".to_vec(), children: vec![Identifier { value: vec![120] }, Identifier { value: vec![121] }] }
+      ]}]}
+    ]}
+  ]}
+);
+
+test!(parser_fn_lambda, r#"fn() { return 1; }"#, lambda,
+  Lambda { children: vec![
+    FunctionArguments { children: vec![] },
+    FunctionStatements { children: vec![
+      FunctionReturn { children: vec![Expression { children: vec![Number{value: 1}]}]}
+    ]}
+  ]}
+);
+
+//testing default parameters and named arguments
+test!(parser_default_parameter, r#"fn connect(host, port = 8080){return port;}"#, function_define,
+  FunctionDefine{
+    name: b"connect".to_vec(),
+    children: vec![
+      FunctionArguments{ children: vec![
+        Identifier { value: b"host".to_vec() },
+        DefaultParameter { children: vec![
+          Identifier { value: b"port".to_vec() },
+          Expression { children: vec![Number{value: 8080}]}
+        ]}
+      ]},
+      FunctionStatements{ children: vec![
+        FunctionReturn{ children: vec![Expression { children: vec![Identifier { value: b"port".to_vec() }]}]}
+      ]}
+    ]
+  }
+);
+
+test!(parser_named_argument, r#"connect(h, port: 5432)"#, function_call,
+  FunctionCall{
+    name: b"connect".to_vec(),
+    children: vec![
+      FunctionArguments{ children: vec![
+        Expression { children: vec![Identifier { value: vec![104] }]},
+        NamedArgument { name: b"port".to_vec(), children: vec![Expression { children: vec![Number{value: 5432}]}]}
+      ]}
+    ],
+    location: Location{line: 1, col: 1}
+  }
+);
+
+//testing lists
+test!(parser_list_index, r#"[1, [2]][1][0]"#, expression,
+  Expression { children: vec![
+    Index { children: vec![
+      Index { children: vec![
+        List { children: vec![
+          Number{value: 1},
+          List { children: vec![Number{value: 2}] }
+        ]},
+        Number{value: 1}
+      ]},
+      Number{value: 0}
+    ]}
+  ]}
+);
+
+test!(parser_index_assignment, r#"xs[i] = 1;"#, statement,
+  Assignment { children: vec![
+    Index { children: vec![Identifier { value: b"xs".to_vec() }, Identifier { value: vec![105] }] },
+    Expression { children: vec![Number{value: 1}]}
+  ]}
+);
+
+test!(parser_map, r#"{"a": 1, b: [], }"#, expression,
+  Expression { children: vec![
+    Map { children: vec![
+      MapEntry { children: vec![String{value: "a".to_string()}, Number{value: 1}] },
+      MapEntry { children: vec![Identifier{value: vec![98]}, List{children: vec![]}] }
+    ]}
+  ]}
+);
+
+test!(parser_block_not_map, r#"{ a; }"#, statement,
+  Block { children: vec![
+    Expression { children: vec![Identifier{value: vec![97]}] }
+  ]}
+);
+
+test!(parser_struct_define, r#"struct Point { x, y }"#, program_item,
+  StructDefine { name: b"Point".to_vec(), children: vec![Identifier{value: vec![120]}, Identifier{value: vec![121]}] }
+);
+
+test!(parser_struct_literal, r#"Point { x: 1, y: p.y }.x"#, expression,
+  Expression { children: vec![
+    Field { name: vec![120], children: vec![
+      StructLiteral { name: b"Point".to_vec(), children: vec![
+        FieldValue { name: vec![120], children: vec![Number{value: 1}] },
+        FieldValue { name: vec![121], children: vec![Field { name: vec![121], children: vec![Identifier{value: vec![112]}] }] }
+      ]}
+    ]}
+  ]}
+);
+
+test!(parser_enum_define, r#"enum Shape { Circle(r), Empty, }"#, program_item,
+  EnumDefine { name: b"Shape".to_vec(), children: vec![
+    VariantDefine { name: b"Circle".to_vec(), children: vec![Identifier{value: vec![114]}] },
+    VariantDefine { name: b"Empty".to_vec(), children: vec![] }
+  ]}
+);
+
+test!(parser_match, r#"match s { Circle(_, -1) => 1, x => 2 }"#, expression,
+  Expression { children: vec![
+    Match { children: vec![
+      Identifier{value: vec![115]},
+      MatchArm { children: vec![
+        VariantPattern { name: b"Circle".to_vec(), children: vec![Wildcard{}, Number{value: -1}] },
+        Expression { children: vec![Number{value: 1}] }
+      ]},
+      MatchArm { children: vec![Identifier{value: vec![120]}, Expression { children: vec![Number{value: 2}] }] }
+    ]}
+  ]}
+);
+
+test!(parser_destructuring, r#"let [a, Point { x, y: _ }, ...rest] = p"#, variable_define,
+  VariableDefine { children: vec![
+    ListPattern { children: vec![
+      Identifier { value: vec![97] },
+      StructPattern { name: b"Point".to_vec(), children: vec![
+        FieldPattern { name: vec![120], children: vec![Identifier { value: vec![120] }] },
+        FieldPattern { name: vec![121], children: vec![Wildcard {}] }
+      ]},
+      RestParameter { children: vec![Identifier { value: b"rest".to_vec() }] }
+    ]},
+    Expression { children: vec![Identifier { value: vec![112] }] }
+  ]}
+);
+
+test!(parser_method_call, r#"p.dist(q).x"#, expression,
+  Expression { children: vec![
+    Field { name: vec![120], children: vec![
+      MethodCall { name: b"dist".to_vec(), children: vec![
+        Identifier { value: vec![112] },
+        FunctionArguments { children: vec![Expression { children: vec![Identifier { value: vec![113] }] }] }
+      ], location: Location{line: 1, col: 2}}
+    ]}
+  ]}
+);
+
+test!(parser_impl_define, r#"impl Point { fn x(self) { self.x } }"#, program_item,
+  ImplDefine { name: b"Point".to_vec(), children: vec![
+    FunctionDefine { name: vec![120], children: vec![
+      FunctionArguments { children: vec![Identifier { value: b"self".to_vec() }] },
+      FunctionStatements { children: vec![Tail { children: vec![Expression { children: vec![Field { name: vec![120], children: vec![Identifier { value: b"self".to_vec() }] }] }] }] }
+    ]}
+  ]}
+);
+
+test!(parser_nil_coalesce, r#"a?.b ?? nil"#, expression,
+  Expression { children: vec![
+    Coalesce { children: vec![
+      OptionalField { name: vec![98], children: vec![Identifier { value: vec![97] }] },
+      Nil {}
+    ]}
+  ]}
+);
+
+test!(parser_try_catch, r#"try { f() } catch (e) { throw e; }"#, statement,
+  TryCatch { children: vec![
+    Block { children: vec![Tail { children: vec![Expression { children: vec![FunctionCall { name: vec![102], children: vec![FunctionArguments { children: vec![] }], location: Location{line: 1, col: 7} }] }] }] },
+    Identifier { value: vec![101] },
+    Block { children: vec![Throw { children: vec![Expression { children: vec![Identifier { value: vec![101] }] }] }] }
+  ]}
+);
+
 //testing if statements within a function
 test!(parser_added_test_7, r#"fn a(){let x = true && false; if (x || true) {return x;} else { return false; }; }"#, function_define, FunctionDefine{
   name: vec![97],
@@ -316,3 +600,95 @@ test!(parser_added_test_7, r#"fn a(){let x = true && false; if (x || true) {retu
 });
 
 
+
+
+//testing syntax errors
+#[test]
+fn parser_error_unconsumed_input() {
+  // `program` no longer stops quietly at input it cannot parse.
+  match program(lex("let x = 1; let = 2;")) {
+    Err(nom::Err::Error(error)) => {
+      assert_eq!((error.line, error.col), (1, 16));
+      assert_eq!(error.found, TokenKind::Equal);
+      assert_eq!(error.expected, vec![TokenKind::LeftBracket, TokenKind::Alpha]);
+    },
+    result => panic!("expected a syntax error, got {:?}", result),
+  }
+}
+
+#[test]
+fn parser_error_recovery() {
+  let source = r#"fn main() {
+  let x = ;
+  let y = 2;
+  return y;
+}
+fn a() { if (1 < 2) { let z = 3 } return 1; }
+fn b() { return (; }
+fn c() { return 1; }
+let m = {"a": 1 "b": 2};
+let p = Point { x: 1 y: 2 };
+fn d() { let f = |x| { x + }; return f; }"#;
+  let errors = parse(lex(source)).unwrap_err();
+  assert_eq!(errors.iter().map(|e| (e.line, e.col, e.found)).collect::<Vec<_>>(), vec![
+    (2, 11, TokenKind::Semicolon),
+    (6, 33, TokenKind::RightCurly),
+    (7, 18, TokenKind::Semicolon),
+    (9, 17, TokenKind::Quote),
+    (10, 22, TokenKind::Alpha),
+    (11, 28, TokenKind::RightCurly),
+  ]);
+  assert!(errors[0].expected.contains(&TokenKind::Digit));
+  assert!(errors[1].expected.contains(&TokenKind::Semicolon));
+}
+
+#[test]
+fn parser_parse_ok() {
+  assert_eq!(parse(lex("fn a(){return 1;} a()")), Ok(Program { children: vec![
+    FunctionDefine{
+      name: vec![97],
+      children: vec![
+        FunctionArguments{ children: vec![] },
+        FunctionStatements{ children: vec![
+          FunctionReturn{ children: vec![
+            Expression { children: vec![Number{value: 1 }]}
+          ]}
+        ]}
+      ]
+    },
+    Expression { children: vec![
+      FunctionCall{name: vec![97], children: vec![FunctionArguments{ children: vec![] }], location: Location{line: 1, col: 19}}
+    ]}
+  ]}));
+}
+
+#[test]
+fn parser_error_messages() {
+  let source = "fn main() {\n  let x = 1\n  return x;\n}";
+  let errors = parse(lex(source)).unwrap_err();
+  assert_eq!(errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(), vec![
+    "expected `;` after statement, found `return` at 3:3",
+  ]);
+  let errors = parse(lex("fn main() { return 1 }")).unwrap_err();
+  assert_eq!(errors[0].to_string(), "expected `;` after statement, found `}` at 1:22");
+  let errors = parse(lex("let z 3;")).unwrap_err();
+  assert_eq!(errors[0].to_string(), "expected `=` after variable name, found `3` at 1:7");
+  let errors = parse(lex("fn main() { return 1;")).unwrap_err();
+  assert_eq!(errors[0].to_string(), "expected `}` after function body, found end of input at 1:22");
+}
+
+#[test]
+fn parser_const_only_at_top_level() {
+  assert!(parse(lex("const LIMIT = 10; fn main() { return LIMIT; }")).is_ok());
+  let errors = parse(lex("fn main() { const LIMIT = 10; return LIMIT; }")).unwrap_err();
+  assert_eq!((errors[0].line, errors[0].col, errors[0].found), (1, 13, TokenKind::Const));
+}
+
+#[test]
+fn parser_rest_parameter_must_be_last() {
+  assert!(parse(lex("fn sum(first, ...rest) { rest }")).is_ok());
+  let errors = parse(lex("fn sum(...rest, last) { rest }")).unwrap_err();
+  assert_eq!(errors[0].to_string(), "expected `)` after parameters, found `,` at 1:15");
+  let errors = parse(lex("fn sum(.. .rest) { rest }")).unwrap_err();
+  assert_eq!((errors[0].line, errors[0].col), (1, 8));
+}